@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use clap::Parser;
+use crescendo::config::{self, Config};
+use crescendo::corpus::CorpusWriter;
+use crescendo::tx_queue::QueuedTx;
+use crescendo::workers;
+use rayon::prelude::*;
+use thousands::Separable;
+
+/// Txs built per chunk, which are then signed in parallel and written in order.
+const CHUNK_SIZE: u64 = 100_000;
+
+/// Pre-generate signed txs the same way crescendo's TxGen workers do, into
+/// a corpus to be sent later with `[tx_source] kind = "corpus"`.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct CliArgs {
+    /// Path to the crescendo config file describing the txs (its [tx_gen_worker] section)
+    config_file: PathBuf,
+    /// Number of txs to generate
+    #[arg(short = 'n', long)]
+    count: u64,
+    /// Path to write the corpus to
+    #[arg(short, long, default_value = "corpus.bin")]
+    output: PathBuf,
+    /// Start from each account's pending nonce on the configured node, instead of 0
+    #[arg(long)]
+    sync_nonces: bool,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = CliArgs::parse();
+
+    println!("[~] Loading config from {}...", args.config_file.display());
+    let config = Config::from_file(&args.config_file)?;
+    config.validate()?;
+    config::init(config);
+
+    workers::init_workloads();
+
+    if args.sync_nonces {
+        println!("[*] Syncing nonces from {}...", config::get().network_worker.rpc_url());
+        workers::sync_nonces().await.map_err(|e| format!("Failed to sync nonces: {e}"))?;
+        workers::sync_workloads().await.map_err(|e| format!("Failed to sync workloads: {e}"))?;
+    }
+
+    let start = Instant::now();
+    let mut writer = CorpusWriter::create(&args.output, config::get().tx_gen_worker.chain_id)?;
+    let mut rng = rand::rng();
+    let mut generated = 0;
+    while generated < args.count {
+        // Build sequentially so every account's txs land in the corpus in nonce order.
+        let chunk: Vec<_> = (0..CHUNK_SIZE.min(args.count - generated)).map(|_| workers::build_tx(&mut rng)).collect();
+        let signed: Vec<QueuedTx> = chunk
+            .into_par_iter()
+            .map(|(account_index, tx)| QueuedTx {
+                sender_index: account_index,
+                raw: workers::sign_and_encode_tx(account_index, tx),
+            })
+            .collect();
+        for tx in &signed {
+            writer.write_tx(tx)?;
+        }
+
+        generated += signed.len() as u64;
+        println!("[*] Generated {} / {} txs", generated.separate_with_commas(), args.count.separate_with_commas());
+    }
+    let written = writer.finish()?;
+
+    println!(
+        "[+] Wrote {} txs to {} in {:.1?}",
+        written.separate_with_commas(),
+        args.output.display(),
+        start.elapsed()
+    );
+    Ok(())
+}
@@ -1,44 +1,246 @@
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::PathBuf;
 
+use alloy::eips::eip2935::{HISTORY_STORAGE_ADDRESS, HISTORY_STORAGE_CODE};
+use alloy::eips::eip4788::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE};
+use alloy::eips::eip7002::{WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS, WITHDRAWAL_REQUEST_PREDEPLOY_CODE};
+use alloy::eips::eip7251::{CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS, CONSOLIDATION_REQUEST_PREDEPLOY_CODE};
+use alloy::eips::eip7840::BlobParams;
+use alloy::genesis::{ChainConfig, Genesis, GenesisAccount};
+use alloy::primitives::{address, Address, Bytes, U256};
 use alloy::signers::local::MnemonicBuilder;
 use alloy::signers::utils::secret_key_to_address;
 use alloy_signer_local::coins_bip39::English;
+use clap::Parser;
+use crescendo::config::{Config, TxGenWorkerConfig, WorkloadKind};
+use crescendo::contracts::{erc20_balance_slot, erc20_runtime_code, pair_runtime_code, pair_storage, ERC20_OWNER_SLOT};
+use crescendo::workers;
 use rayon::prelude::*;
-use serde::{Deserialize, Serialize};
 use simple_tqdm::ParTqdm;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AccountBalance {
-    balance: String,
+const DEFAULT_TOKEN1_ADDRESS: Address = address!("2000000000000000000000000000000000000002");
+const DEFAULT_PAIR_ADDRESS: Address = address!("2000000000000000000000000000000000000003");
+
+const GAS_LIMIT: u64 = 3_000_000_000;
+const INITIAL_BASE_FEE: u128 = 1_000_000_000; // 1 gwei
+
+/// Generate a genesis funding crescendo's accounts, with the bundled ERC20 at the token address and every
+/// account holding a balance of it, ready to pass to reth/geth `--chain`. With a swap workload configured
+/// (or its addresses passed), a second ERC20 and an AMM pair between the two are pre-deployed too.
+///
+/// Accounts and the token address are taken from the config file's [tx_gen_worker] section, the AMM's
+/// from its swap workload and [bootstrap] section, with any of the flags below overriding them. The tokens
+/// are owned by the first funded account, i.e. the one at --start-index, unless --owner is passed.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct CliArgs {
+    /// Path to a crescendo config file to read mnemonic, num_accounts, chain_id and token_contract_address from
+    #[arg(default_value = "configs/default.toml")]
+    config_file: PathBuf,
+    /// Number of accounts to fund [default: the config's num_accounts]
+    #[arg(short = 'n', long)]
+    num_accounts: Option<u32>,
+    /// Mnemonic to derive the accounts from [default: the config's mnemonic]
+    #[arg(long)]
+    mnemonic: Option<String>,
+    /// Index of the first account, among those derived from the mnemonic or listed in private_keys_path
+    #[arg(long, default_value_t = 0)]
+    start_index: u32,
+    /// Chain id of the genesis, ignored with --merge [default: the config's chain_id]
+    #[arg(long)]
+    chain_id: Option<u64>,
+    /// Balance of each account, in wei
+    #[arg(long, default_value = "1000000000000000000000000")]
+    balance: U256,
+    /// Address to pre-deploy the bundled ERC20 at [default: the config's token_contract_address]
+    #[arg(long)]
+    token_address: Option<Address>,
+    /// Address to pre-deploy the swap workload's second ERC20 at [default: the swap workload's token1, else
+    /// 0x2000000000000000000000000000000000000002]
+    #[arg(long)]
+    token1_address: Option<Address>,
+    /// Address to pre-deploy the swap workload's AMM pair at [default: the swap workload's pair, else
+    /// 0x2000000000000000000000000000000000000003]
+    #[arg(long)]
+    pair_address: Option<Address>,
+    /// Token balance of each account, in both tokens
+    #[arg(long, default_value = "1000000000000000000")]
+    token_balance: U256,
+    /// Owner of the tokens, the only account allowed to mint them [default: the account at --start-index]
+    #[arg(long)]
+    owner: Option<Address>,
+    /// Reserves of each token the AMM pair holds [default: the config's amm_liquidity]
+    #[arg(long)]
+    pair_reserve: Option<U256>,
+    /// Path to write the genesis to, e.g. utils/genesis.json for utils/start-reth.sh
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Add the accounts and token to the existing genesis at the output path, keeping its chain config
+    #[arg(long)]
+    merge: bool,
+    /// Replace an existing genesis at the output path
+    #[arg(long, conflicts_with = "merge")]
+    force: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    const NUM_ACCOUNTS: u32 = 50_000;
-    const MNEMONIC: &str = "test test test test test test test test test test test junk";
+    let args = CliArgs::parse();
+
+    if args.output.exists() && !args.merge && !args.force {
+        return Err(format!(
+            "{} already exists, pass --force to replace it or --merge to add to it",
+            args.output.display()
+        )
+        .into());
+    }
+
+    println!("Loading config from {}...", args.config_file.display());
+    let Config { tx_gen_worker: config, workloads, bootstrap, .. } = Config::from_file(&args.config_file)?;
+    let num_accounts = args.num_accounts.unwrap_or(config.num_accounts);
+    let mnemonic = args.mnemonic.unwrap_or(config.mnemonic);
+    let chain_id = args.chain_id.unwrap_or(config.chain_id);
+    let token_address = match args.token_address {
+        Some(token_address) => token_address,
+        None => config.token_contract_address.parse()?,
+    };
+    let swap = workloads.into_iter().find_map(|workload| match workload.kind {
+        WorkloadKind::Swap { pair, token1, .. } => Some((pair, token1)),
+        _ => None,
+    });
+    let amm = if swap.is_some() || args.token1_address.is_some() || args.pair_address.is_some() {
+        let (pair, token1) = swap.unwrap_or_default();
+        let token1_address = match (args.token1_address, token1) {
+            (Some(token1_address), _) => token1_address,
+            (None, Some(token1)) => token1.parse()?,
+            (None, None) => DEFAULT_TOKEN1_ADDRESS,
+        };
+        let pair_address = match (args.pair_address, pair) {
+            (Some(pair_address), _) => pair_address,
+            (None, Some(pair)) => pair.parse()?,
+            (None, None) => DEFAULT_PAIR_ADDRESS,
+        };
+        Some((token1_address, pair_address, args.pair_reserve.unwrap_or(bootstrap.amm_liquidity)))
+    } else {
+        None
+    };
+
+    let mut genesis = if args.merge {
+        println!("Merging into {}...", args.output.display());
+        serde_json::from_str(&fs::read_to_string(&args.output)?)?
+    } else {
+        new_genesis(chain_id)
+    };
 
-    println!("Generating {NUM_ACCOUNTS} accounts...");
+    println!("Generating {num_accounts} accounts from index {}...", args.start_index);
 
-    let genesis_alloc: BTreeMap<String, AccountBalance> = (0..NUM_ACCOUNTS)
-        .into_par_iter()
-        .tqdm()
-        .map(|worker_id| {
-            let signer =
-                MnemonicBuilder::<English>::default().phrase(MNEMONIC).index(worker_id).unwrap().build().unwrap();
+    let accounts: Vec<Address> = if config.private_keys_path.is_some() || config.signer_cache_path.is_some() {
+        // Fund the keys crescendo will sign with, reading (and filling) the cache the same way it does.
+        let signers = workers::load_signers(&TxGenWorkerConfig {
+            mnemonic,
+            num_accounts: args.start_index + num_accounts,
+            ..config
+        });
+        signers[args.start_index as usize..].iter().map(|signer| signer.address()).collect()
+    } else {
+        (args.start_index..args.start_index + num_accounts)
+            .into_par_iter()
+            .tqdm()
+            .map(|index| {
+                let signer =
+                    MnemonicBuilder::<English>::default().phrase(&mnemonic).index(index).unwrap().build().unwrap();
 
-            let address = secret_key_to_address(signer.credential());
+                secret_key_to_address(signer.credential())
+            })
+            .collect()
+    };
 
-            (format!("{address:?}"), AccountBalance { balance: "0xD3C21BCECCEDA1000000".to_string() })
-        })
-        .collect();
+    let alloc = &mut genesis.alloc;
+    for &address in &accounts {
+        alloc.entry(address).or_default().balance = args.balance;
+    }
 
-    let output_path = Path::new("genesis-alloc.json");
-    let json = serde_json::to_string_pretty(&genesis_alloc)?;
-    fs::write(output_path, json)?;
+    // The bundled ERC20s, with every account's balance pre-set, and the AMM pair's reserves if there is one.
+    let owner = args.owner.or(accounts.first().copied());
+    for token_address in [Some(token_address), amm.map(|(token1_address, ..)| token1_address)].into_iter().flatten() {
+        let token = alloc.entry(token_address).or_default();
+        token.code = Some(erc20_runtime_code());
+        let token_storage = token.storage.get_or_insert_default();
+        token_storage
+            .par_extend(accounts.par_iter().map(|&address| (erc20_balance_slot(address), args.token_balance.into())));
+        if let Some((_, pair_address, pair_reserve)) = amm {
+            token_storage.insert(erc20_balance_slot(pair_address), pair_reserve.into());
+        }
+        if let Some(owner) = owner {
+            token_storage.entry(ERC20_OWNER_SLOT).or_insert(owner.into_word());
+        }
+    }
 
-    println!("\nSuccessfully generated {NUM_ACCOUNTS} accounts!");
-    println!("Accounts saved to: {}", output_path.display());
+    // The AMM pair between them, holding its reserves of both.
+    if let Some((token1_address, pair_address, pair_reserve)) = amm {
+        let pair = alloc.entry(pair_address).or_default();
+        pair.code = Some(pair_runtime_code());
+        pair.storage.get_or_insert_default().extend(pair_storage(
+            token_address,
+            token1_address,
+            pair_reserve,
+            pair_reserve,
+        ));
+    }
+
+    // Cancun and Prague blocks call these system contracts, and are invalid without the latter two.
+    for (address, code) in [
+        (BEACON_ROOTS_ADDRESS, &BEACON_ROOTS_CODE),
+        (HISTORY_STORAGE_ADDRESS, &HISTORY_STORAGE_CODE),
+        (WITHDRAWAL_REQUEST_PREDEPLOY_ADDRESS, &WITHDRAWAL_REQUEST_PREDEPLOY_CODE),
+        (CONSOLIDATION_REQUEST_PREDEPLOY_ADDRESS, &CONSOLIDATION_REQUEST_PREDEPLOY_CODE),
+    ] {
+        alloc
+            .entry(address)
+            .or_insert_with(|| GenesisAccount::default().with_nonce(Some(1)).with_code(Some(Bytes::clone(code))));
+    }
+
+    let json = serde_json::to_string_pretty(&genesis)?;
+    fs::write(&args.output, json)?;
+
+    println!("\nSuccessfully generated a genesis with {num_accounts} accounts!");
+    println!("Genesis saved to: {}", args.output.display());
 
     Ok(())
 }
+
+fn new_genesis(chain_id: u64) -> Genesis {
+    Genesis {
+        config: ChainConfig {
+            chain_id,
+            // Every fork up to Prague is active from genesis.
+            homestead_block: Some(0),
+            eip150_block: Some(0),
+            eip155_block: Some(0),
+            eip158_block: Some(0),
+            byzantium_block: Some(0),
+            constantinople_block: Some(0),
+            petersburg_block: Some(0),
+            istanbul_block: Some(0),
+            berlin_block: Some(0),
+            london_block: Some(0),
+            merge_netsplit_block: Some(0),
+            shanghai_time: Some(0),
+            cancun_time: Some(0),
+            prague_time: Some(0),
+            terminal_total_difficulty: Some(U256::ZERO),
+            terminal_total_difficulty_passed: true,
+            blob_schedule: BTreeMap::from([
+                ("cancun".to_string(), BlobParams::cancun()),
+                ("prague".to_string(), BlobParams::prague()),
+            ]),
+            ..Default::default()
+        },
+        gas_limit: GAS_LIMIT,
+        difficulty: U256::ZERO,
+        base_fee_per_gas: Some(INITIAL_BASE_FEE),
+        excess_blob_gas: Some(0),
+        blob_gas_used: Some(0),
+        ..Default::default()
+    }
+}
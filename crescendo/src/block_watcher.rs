@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use alloy::primitives::{B256, U64};
+use dashmap::DashMap;
+use hdrhistogram::Histogram;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use thousands::Separable;
+
+use crate::config;
+use crate::rpc::RpcClient;
+
+/// Follows new blocks on the target node, matching their txs against the ones we
+/// submitted to measure how many actually land on chain and how long that takes.
+pub struct BlockWatcher {
+    submitted: DashMap<B256, Instant>, // Hash -> submission time, for txs not yet seen in a block.
+    // Hash -> time its block was observed, for txs in blocks not (yet) known to be ours. A tx can land in
+    // a block before the node's reply to it is processed, so these are kept for request_timeout_ms.
+    recent_block_txs: DashMap<B256, Instant>,
+    included_txs: AtomicU64,
+    block_gas: AtomicU64, // Of every block, including other senders' txs on a shared chain.
+    never_included: AtomicU64,
+    inclusion_latencies: Mutex<Histogram<u64>>, // Milliseconds, for the whole run.
+}
+
+pub static BLOCK_WATCHER: LazyLock<BlockWatcher> = LazyLock::new(|| BlockWatcher {
+    submitted: DashMap::new(),
+    recent_block_txs: DashMap::new(),
+    included_txs: AtomicU64::new(0),
+    block_gas: AtomicU64::new(0),
+    never_included: AtomicU64::new(0),
+    inclusion_latencies: Mutex::new(new_inclusion_histogram()),
+});
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockSummary {
+    gas_used: U64,
+    transactions: Vec<B256>, // Hashes only, as we don't request full txs.
+}
+
+/// Histogram of inclusion latencies in milliseconds, from 1ms to 1 hour at 3 significant figures.
+pub fn new_inclusion_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, 3_600_000, 3).unwrap()
+}
+
+impl BlockWatcher {
+    /// Record that a tx was accepted by the node, to be matched against future blocks.
+    pub fn record_submitted(&self, hash: B256, submitted_at: Instant) {
+        self.submitted.insert(hash, submitted_at);
+        // Its block may have been processed first. Whichever of the two takes it out of recent_block_txs
+        // after inserting into its own map records the inclusion, so it's recorded exactly once.
+        if let Some((_, observed_at)) = self.recent_block_txs.remove(&hash) {
+            self.record_included(&hash, observed_at);
+        }
+    }
+
+    pub fn total_included_txs(&self) -> u64 {
+        self.included_txs.load(Ordering::Relaxed)
+    }
+
+    /// Gas used by all blocks observed, which only matches our txs' on a chain no one else is sending to.
+    pub fn total_block_gas(&self) -> u64 {
+        self.block_gas.load(Ordering::Relaxed)
+    }
+
+    pub fn total_never_included(&self) -> u64 {
+        self.never_included.load(Ordering::Relaxed)
+    }
+
+    /// Number of submitted txs not yet seen in a block (nor timed out).
+    pub fn pending_txs(&self) -> usize {
+        self.submitted.len()
+    }
+
+    /// Snapshot of all inclusion latencies (in milliseconds) recorded so far.
+    pub fn run_inclusion_latencies(&self) -> Histogram<u64> {
+        self.inclusion_latencies.lock().clone()
+    }
+
+    /// Inclusion latencies recorded since `snapshot` was taken, which is then updated to the
+    /// current run latencies, so each caller can track its own measurement interval.
+    pub fn inclusion_latencies_since(&self, snapshot: &mut Histogram<u64>) -> Histogram<u64> {
+        let run_latencies = self.run_inclusion_latencies();
+        let mut latencies = run_latencies.clone();
+        latencies.subtract(&*snapshot).unwrap(); // The run's latencies only ever grow.
+        *snapshot = run_latencies;
+        latencies
+    }
+
+    /// Poll the target node for new blocks forever, recording inclusion of submitted txs.
+    ///
+    /// Note: Inclusion latency is measured when a block is observed, so it's
+    /// overestimated by up to poll_interval_ms plus the block fetch time.
+    pub async fn start_watcher(&self) {
+        let config = &config::get().block_watcher;
+        let rpc = RpcClient::new(config::get().network_worker.rpc_url());
+
+        // Only blocks mined after startup can include our txs.
+        let mut next_block = loop {
+            match rpc.request::<U64>("eth_blockNumber", serde_json::json!([])).await {
+                Ok(number) => break number.to::<u64>() + 1,
+                Err(e) => eprintln!("[!] Block watcher failed to get block number: {e}"),
+            }
+            tokio::time::sleep(Duration::from_millis(config.poll_interval_ms)).await;
+        };
+
+        let mut interval = tokio::time::interval(Duration::from_millis(config.poll_interval_ms));
+        loop {
+            interval.tick().await;
+
+            let latest_block = match rpc.request::<U64>("eth_blockNumber", serde_json::json!([])).await {
+                Ok(number) => number.to::<u64>(),
+                Err(e) => {
+                    eprintln!("[!] Block watcher failed to get block number: {e}");
+                    continue;
+                }
+            };
+            if latest_block < next_block {
+                continue;
+            }
+
+            // Fetch every block we haven't seen yet in one batch, capped to avoid giant responses.
+            let last_block = latest_block.min(next_block + config.max_blocks_per_poll - 1);
+            let calls = (next_block..=last_block).map(|number| serde_json::json!([U64::from(number), false])).collect();
+            let responses = match rpc.batch_request("eth_getBlockByNumber", calls).await {
+                Ok(responses) => responses,
+                Err(e) => {
+                    eprintln!("[!] Block watcher failed to fetch blocks {next_block}..={last_block}: {e}");
+                    continue;
+                }
+            };
+
+            let observed_at = Instant::now();
+            for res in responses {
+                let Some(block) = res.result.and_then(|block| serde_json::from_value::<BlockSummary>(block).ok())
+                else {
+                    break; // Not available yet (or malformed), retry from here next poll.
+                };
+                self.process_block(&block, observed_at);
+                next_block += 1;
+            }
+        }
+    }
+
+    fn process_block(&self, block: &BlockSummary, observed_at: Instant) {
+        for hash in &block.transactions {
+            if !self.record_included(hash, observed_at) {
+                self.recent_block_txs.insert(*hash, observed_at);
+                // Recheck in case the reply to it was recorded in between (see record_submitted).
+                if self.submitted.contains_key(hash) && self.recent_block_txs.remove(hash).is_some() {
+                    self.record_included(hash, observed_at);
+                }
+            }
+        }
+        self.block_gas.fetch_add(block.gas_used.to::<u64>(), Ordering::Relaxed);
+    }
+
+    /// Record a submitted tx as included in a block observed at `observed_at`. Returns false if it isn't
+    /// one we've recorded submitting (yet).
+    fn record_included(&self, hash: &B256, observed_at: Instant) -> bool {
+        let Some((_, submitted_at)) = self.submitted.remove(hash) else {
+            return false;
+        };
+        let latency = observed_at.saturating_duration_since(submitted_at);
+        self.inclusion_latencies.lock().saturating_record(latency.as_millis() as u64);
+        self.included_txs.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Forget submitted txs that have been waiting longer than the inclusion timeout, counting them as
+    /// never included, and block txs no reply can still be recorded for.
+    fn evict_timed_out(&self) {
+        let request_timeout = Duration::from_millis(config::get().network_worker.request_timeout_ms);
+        self.recent_block_txs.retain(|_, observed_at| observed_at.elapsed() < request_timeout);
+
+        let timeout = Duration::from_secs(config::get().block_watcher.inclusion_timeout_secs);
+        let mut evicted = 0;
+        self.submitted.retain(|_, submitted_at| {
+            let keep = submitted_at.elapsed() < timeout;
+            evicted += !keep as u64;
+            keep
+        });
+        self.never_included.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    pub async fn start_reporter(&self, measurement_interval: Duration) {
+        let mut last_included_txs = 0u64;
+        let mut last_block_gas = 0u64;
+        let mut last_latencies = new_inclusion_histogram();
+        let mut interval = tokio::time::interval(measurement_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            self.evict_timed_out();
+            let included_txs = self.total_included_txs();
+            let block_gas = self.total_block_gas();
+            let tps = (included_txs - last_included_txs) / measurement_interval.as_secs();
+            let gas_per_second = (block_gas - last_block_gas) / measurement_interval.as_secs();
+            println!(
+                "[*] Included TPS: {}, Block gas/s: {}, Total included: {}, Pending: {}, Never included: {}",
+                tps.separate_with_commas(),
+                gas_per_second.separate_with_commas(),
+                included_txs.separate_with_commas(),
+                self.pending_txs().separate_with_commas(),
+                self.total_never_included().separate_with_commas()
+            );
+
+            let interval_latencies = self.inclusion_latencies_since(&mut last_latencies);
+            if !interval_latencies.is_empty() {
+                println!("[*] Inclusion latency {}", format_inclusion_percentiles(&interval_latencies));
+                println!("[*] Inclusion latency (run) {}", format_inclusion_percentiles(&last_latencies));
+            }
+
+            last_included_txs = included_txs;
+            last_block_gas = block_gas;
+        }
+    }
+}
+
+/// Format a histogram's percentiles, e.g. "p50: 1.2s, p90: 3.4s, ...".
+fn format_inclusion_percentiles(histogram: &Histogram<u64>) -> String {
+    let format_millis = |millis: u64| format!("{:.1?}", Duration::from_millis(millis));
+    [50.0, 90.0, 99.0]
+        .iter()
+        .map(|percentile| format!("p{percentile}: {}", format_millis(histogram.value_at_percentile(*percentile))))
+        .chain(std::iter::once(format!("max: {}", format_millis(histogram.max()))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,6 +1,8 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::OnceLock;
 
+use alloy::primitives::U256;
 use serde::{Deserialize, Serialize};
 
 use crate::utils::merge_toml_values;
@@ -19,14 +21,29 @@ pub fn get() -> &'static Config {
     CONFIG_INSTANCE.wait()
 }
 
+/// Initialize the global configuration instance from configs/default.toml, unless already initialized.
+#[cfg(test)]
+pub fn init_default() {
+    CONFIG_INSTANCE.get_or_init(|| Config::from_file(&PathBuf::from(DEFAULT_CONFIG_PATH)).unwrap());
+}
+
+#[cfg(test)]
+const DEFAULT_CONFIG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../configs/default.toml");
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    pub tx_source: TxSourceConfig,
     pub tx_gen_worker: TxGenWorkerConfig,
+    pub workloads: Vec<WorkloadConfig>,
+    pub bootstrap: BootstrapConfig,
     pub network_worker: NetworkWorkerConfig,
     pub rate_limiting: RateLimitingConfig,
+    pub load_profile: LoadProfileConfig,
+    pub block_watcher: BlockWatcherConfig,
 
     pub workers: WorkersConfig,
     pub reporters: ReportersConfig,
+    pub run: RunConfig,
 }
 
 impl Config {
@@ -56,40 +73,327 @@ impl Config {
 
         Ok(config_value.try_into()?)
     }
+
+    /// Check settings that parse but can't be run with, describing the first one found.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.network_worker.transport != Transport::Http && self.network_worker.rpc_url.is_none() {
+            return Err(
+                "network_worker.rpc_url must be set to an HTTP endpoint when not using the http transport.".into()
+            );
+        }
+
+        if !(0.0..=1.0).contains(&self.tx_gen_worker.eip1559_fraction) {
+            return Err("tx_gen_worker.eip1559_fraction must be between 0 and 1.".into());
+        }
+
+        if self.rate_limiting.mode == RateLimitMode::Adaptive {
+            let adaptive = &self.rate_limiting.adaptive;
+            if adaptive.min_ratelimit == 0 || adaptive.min_ratelimit > adaptive.max_ratelimit {
+                return Err("rate_limiting.adaptive.min_ratelimit must be non-zero and at most max_ratelimit.".into());
+            }
+            if adaptive.max_error_rate <= 0.0 || adaptive.max_latency_p99_ms == 0 || adaptive.sustain_intervals == 0 {
+                return Err("rate_limiting.adaptive targets and sustain_intervals must be non-zero.".into());
+            }
+            if adaptive.max_inclusion_lag_p99_ms.is_some() && !self.block_watcher.enabled {
+                return Err(
+                    "block_watcher must be enabled to use rate_limiting.adaptive.max_inclusion_lag_p99_ms.".into()
+                );
+            }
+        }
+
+        if self.rate_limiting.mode == RateLimitMode::Profile {
+            let load_profile = &self.load_profile;
+            if load_profile.segments.is_empty() || load_profile.update_interval_ms == 0 {
+                return Err("load_profile.segments must be non-empty and update_interval_ms non-zero.".into());
+            }
+            if let Some(segment) = load_profile.segments.iter().find(|segment| !segment.is_valid()) {
+                return Err(format!("Invalid load profile segment: {segment:?}"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxSourceConfig {
+    pub kind: TxSourceKind,
+    pub corpus_path: Option<PathBuf>,
+    pub max_queued_txs: usize,
+
+    pub replay_path: Option<PathBuf>,
+    pub replay_format: ReplayFormat,
+    pub resign: bool,
+    pub rewrite_chain_id: bool,
+    pub rewrite_nonces: bool,
+}
+
+impl TxSourceConfig {
+    /// Whether txs are sent from our accounts at nonces we track, which can then be synced from the node.
+    pub fn tracks_nonces(&self) -> bool {
+        match self.kind {
+            TxSourceKind::Generate => true,
+            TxSourceKind::Corpus => false,
+            TxSourceKind::Replay => self.resign && self.rewrite_nonces,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxSourceKind {
+    Generate,
+    Corpus,
+    Replay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayFormat {
+    Hex,
+    Binary,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkWorkerConfig {
+    pub transport: Transport,
     pub target_url: String,
+    pub rpc_url: Option<String>,
     pub total_connections: u64,
 
     pub batch_factor: usize,
+    pub max_in_flight_requests: usize,
+    pub request_timeout_ms: u64,
 
     pub error_sleep_ms: u64,
     pub tx_queue_empty_sleep_ms: u64,
 }
 
+impl NetworkWorkerConfig {
+    /// HTTP endpoint for calls other than tx submission (nonce syncing, block watching, etc).
+    pub fn rpc_url(&self) -> &str {
+        self.rpc_url.as_deref().unwrap_or(&self.target_url)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Http,
+    Ws,
+    Ipc,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TxGenWorkerConfig {
     pub chain_id: u64,
 
     pub mnemonic: String,
+    pub private_keys_path: Option<PathBuf>,
+    pub signer_cache_path: Option<PathBuf>,
     pub num_accounts: u32,
+    pub signing_backend: SigningBackend,
 
     pub gas_price: u64,
     pub gas_limit: u64,
 
+    pub eip1559_fraction: f64,
+    pub max_fee_per_gas: u64,
+    pub max_priority_fee_per_gas: u64,
+
     pub token_contract_address: String,
     pub recipient_distribution_factor: u32,
+    pub sender_distribution: AccountDistribution,
+    pub recipient_distribution: AccountDistribution,
     pub max_transfer_amount: u64,
+    pub access_lists: bool,
 
     pub batch_size: u32,
+
+    pub sync_nonces_on_startup: bool,
+    pub resync_nonces_on_error: bool,
+    pub nonce_sync_batch_size: usize,
+    pub nonce_sync_concurrency: usize,
+}
+
+/// How accounts are picked among those eligible, from spreading txs evenly over them
+/// to concentrating them on a few, whose state the txs then all conflict on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccountDistribution {
+    /// Every account equally likely.
+    Uniform,
+    /// The account at index k picked with probability proportional to 1 / (k + 1)^exponent.
+    Zipf { exponent: f64 },
+    /// One of the first hot_accounts accounts picked hot_fraction of the time, one of the rest otherwise.
+    Hotspot { hot_accounts: u32, hot_fraction: f64 },
+    /// Each TxGen worker picks uniformly from its own disjoint slice of the senders, recipients from the start of it.
+    Partitioned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapConfig {
+    pub enabled: bool,
+    pub token_init_code: Option<String>,
+    pub mint_amount: u64,
+    pub mint_batch_size: usize,
+    pub deploy_gas_limit: u64,
+    pub receipt_timeout_secs: u64,
+    pub amm_liquidity: U256,
+}
+
+/// A kind of tx to generate, picked for each tx with probability proportional to its weight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkloadConfig {
+    pub weight: f64,
+    pub gas_limit: Option<u64>, // Defaults to tx_gen_worker.gas_limit (or the minimum, for native transfers).
+    #[serde(flatten)]
+    pub kind: WorkloadKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorkloadKind {
+    /// Transfer of the tx_gen_worker.token_contract_address token to another account.
+    Erc20Transfer,
+    /// Plain value transfer of min_value..=max_value wei to another account (21_000 gas unless gas_limit is set).
+    NativeTransfer { min_value: u64, max_value: u64, recipient_distribution_factor: Option<u32> },
+    /// Contract creation running `init_code`, by default a storage writer contract (see contract_call).
+    ContractDeploy { init_code: Option<String> },
+    /// Call to `to` with `input`, followed by `random_words` random 32 byte words.
+    ContractCall { to: String, input: Option<String>, random_words: Option<u32> },
+    /// EIP-4844 tx carrying blobs_per_tx blobs of random data, or `data` split across as many as it needs,
+    /// sent from `accounts` accounts reserved for blob txs if set.
+    Blob {
+        accounts: Option<u32>,
+        blobs_per_tx: Option<u32>,
+        data: Option<String>,
+        num_payloads: Option<usize>,
+        max_fee_per_blob_gas: Option<u64>,
+    },
+    /// EIP-7702 tx with authorizations_per_tx authorizations delegating to `delegate`, from random accounts among
+    /// `authorities` reserved for them, calling the first authority with `call_input` if set, else sent to a
+    /// random recipient.
+    SetCode {
+        delegate: String,
+        authorities: Option<u32>,
+        authorizations_per_tx: Option<u32>,
+        call_input: Option<String>,
+    },
+    /// Swap of min_amount_in..=max_amount_in of a random side of the AMM pair between the token and
+    /// `token1` (both deployed by the bootstrap if it's enabled), after approving it once per account.
+    Swap { pair: Option<String>, token1: Option<String>, min_amount_in: u64, max_amount_in: u64 },
+}
+
+impl WorkloadKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkloadKind::Erc20Transfer => "erc20_transfer",
+            WorkloadKind::NativeTransfer { .. } => "native_transfer",
+            WorkloadKind::ContractDeploy { .. } => "contract_deploy",
+            WorkloadKind::ContractCall { .. } => "contract_call",
+            WorkloadKind::Blob { .. } => "blob",
+            WorkloadKind::SetCode { .. } => "set_code",
+            WorkloadKind::Swap { .. } => "swap",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningBackend {
+    K256,
+    Secp256k1,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RateLimitingConfig {
+    pub mode: RateLimitMode,
     pub initial_ratelimit: u64,
     pub ratelimit_thresholds: Vec<(u32, u64)>,
+    pub adaptive: AdaptiveRateLimitConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitMode {
+    Thresholds,
+    Adaptive,
+    Profile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaptiveRateLimitConfig {
+    pub min_ratelimit: u64,
+    pub max_ratelimit: u64,
+
+    pub max_error_rate: f64,
+    pub max_latency_p99_ms: u64,
+    pub max_inclusion_lag_p99_ms: Option<u64>,
+
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub max_step_factor: f64,
+
+    pub sustain_intervals: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadProfileConfig {
+    pub update_interval_ms: u64,
+    pub on_end: LoadProfileEnd,
+    pub segments: Vec<LoadSegment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadProfileEnd {
+    Stop,
+    Hold,
+    Repeat,
+}
+
+/// A stretch of a load profile, with rates in txs/s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum LoadSegment {
+    #[serde(alias = "soak")]
+    Hold {
+        rate: u64,
+        duration_secs: f64,
+    },
+    Ramp {
+        from: u64,
+        to: u64,
+        duration_secs: f64,
+    },
+    Step {
+        from: u64,
+        to: u64,
+        steps: u32,
+        duration_secs: f64,
+    },
+    Spike {
+        base: u64,
+        peak: u64,
+        spike_secs: f64,
+        duration_secs: f64,
+    },
+    Sine {
+        mean: u64,
+        amplitude: u64,
+        period_secs: f64,
+        duration_secs: f64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockWatcherConfig {
+    pub enabled: bool,
+    pub poll_interval_ms: u64,
+    pub max_blocks_per_poll: u64,
+    pub inclusion_timeout_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,4 +407,41 @@ pub struct WorkersConfig {
 pub struct ReportersConfig {
     pub tx_queue_report_interval_secs: u64,
     pub network_stats_report_interval_secs: u64,
+    pub block_watcher_report_interval_secs: u64,
+
+    pub prometheus_listen_addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunConfig {
+    pub duration_secs: Option<u64>,
+    pub max_total_txs: Option<u64>,
+    pub drain_queue_on_stop: bool,
+    pub summary_json_path: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_config() -> Config {
+        Config::from_file(&PathBuf::from(DEFAULT_CONFIG_PATH)).unwrap()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(default_config().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_unrunnable_settings() {
+        let mut config = default_config();
+        config.tx_gen_worker.eip1559_fraction = 1.5;
+        assert_eq!(config.validate().unwrap_err(), "tx_gen_worker.eip1559_fraction must be between 0 and 1.");
+
+        let mut config = default_config();
+        config.rate_limiting.mode = RateLimitMode::Profile;
+        config.load_profile.segments.clear();
+        assert!(config.validate().unwrap_err().starts_with("load_profile.segments must be non-empty"));
+    }
 }
@@ -0,0 +1,271 @@
+use alloy::primitives::{hex, keccak256, Address, Bytes, B256, U256};
+use alloy::sol;
+use alloy::sol_types::SolValue;
+
+sol! {
+    /// The interface of the bundled ERC20, which is all crescendo needs of a token.
+    interface ERC20 {
+        function transfer(address to, uint256 amount) external returns (bool);
+        function transferFrom(address from, address to, uint256 amount) external returns (bool);
+        function approve(address spender, uint256 amount) external returns (bool);
+        function balanceOf(address account) external view returns (uint256);
+        function allowance(address owner, address spender) external view returns (uint256);
+        function mint(address[] to, uint256 amount) external;
+    }
+
+    /// The interface of the bundled AMM pair.
+    interface Pair {
+        function swap(uint256 amountIn, bool zeroForOne) external returns (uint256 amountOut);
+        function getReserves() external view returns (uint256 reserve0, uint256 reserve1);
+    }
+}
+
+/// Runtime code of the bundled ERC20, hand-assembled so building crescendo doesn't need solc. Its storage
+/// layout matches a Solidity contract with `mapping(address => uint256) balances` at slot 0, `owner` at
+/// slot 1 and `mapping(address => mapping(address => uint256)) allowances` at slot 2:
+///
+///   transfer(to, amount):            reverts if balances[caller] < amount, moves amount from balances[caller]
+///                                    to balances[to], logs Transfer(caller, to, amount) and returns true.
+///   transferFrom(from, to, amount):  reverts if allowances[from][caller] < amount, deducts amount from it,
+///                                    then does the same as transfer, from `from`.
+///   approve(spender, amount):        sets allowances[caller][spender] to amount, logs Approval(caller,
+///                                    spender, amount) and returns true.
+///   balanceOf(account):              returns balances[account].
+///   allowance(owner, spender):       returns allowances[owner][spender].
+///   mint(to[], amount):              reverts unless called by owner, adds amount to balances[to[i]] for each i.
+///
+/// Any other call reverts. There's no total supply or overflow checking.
+pub const ERC20_RUNTIME_CODE: &str = "60003560e01c8063a9059cbb146100a057806323b872dd1461006157806370a082311461004c5780\
+                                      63095ea7b314610105578063dd62ed3e146101995763ea66696c1461015b575b600080fd5b600435\
+                                      60005260406000205460005260206000f35b60043560005260026020526040600020602052336000\
+                                      5260406000208054604435808210610047579003905560006020526044356024356004356100a856\
+                                      5b602435600435335b80600052604060002080548481106100475784900390558160005260406000\
+                                      2080548401905591600052907fddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a\
+                                      4df523b3ef60206000a35b600160005260206000f35b336000526002602052604060002060205260\
+                                      043560005260406000206024359055602435600052600435337f8c5be1e5ebec7d5bd14f71427d1e\
+                                      84f3dd0314c0f7b2291e5b200ac8c7c3b92560206000a36100fa565b600154331415610047576024\
+                                      35600435600401803560051b8101905b818110156101975760200180356000526040600020805484\
+                                      019055610177565b005b600435600052600260205260406000206020526024356000526040600020\
+                                      5460005260206000f3";
+
+/// Constructor of the bundled ERC20, which sets owner to the deployer and returns the runtime code appended
+/// to it: CALLER PUSH1 1 SSTORE PUSH2 449 DUP1 PUSH2 17 PUSH1 0 CODECOPY PUSH1 0 RETURN.
+const ERC20_CONSTRUCTOR: &str = "336001556101c1806100116000396000f3";
+
+/// Slot of the bundled ERC20's owner, the only account allowed to mint.
+pub const ERC20_OWNER_SLOT: B256 = B256::with_last_byte(1);
+
+/// Runtime code of the bundled constant product AMM pair, hand-assembled like the ERC20. Stores token0
+/// at slot 0, token1 at slot 1 and their reserves at slots 2 and 3:
+///
+///   swap(amountIn, zeroForOne):  with (in, out) = (0, 1) if zeroForOne else (1, 0), computes amountOut =
+///                                amountIn * 997 * reserve_out / (reserve_in * 1000 + amountIn * 997), reverting
+///                                if it's 0, updates both reserves, then pulls amountIn of token_in from the
+///                                caller with transferFrom and sends them amountOut of token_out.
+///   getReserves():               returns (reserve0, reserve1).
+///
+/// Any other call reverts. The reserves only change through swaps, so there's no adding or removing
+/// liquidity beyond what the pair is deployed with.
+pub const PAIR_RUNTIME_CODE: &str = "60003560e01c80632aea66051461003257630902f1ac14610020575b600080fd5b60025460005260\
+                                     035460205260406000f35b602435158060011860043582600201548260020154826103e502808202\
+                                     90836103e802019004801561001b578082038560020155905090820184600201556323b872dd60e0\
+                                     1b6000523360045230602452816044526000600060646000600088545af11561001b5763a9059cbb\
+                                     60e01b60005233600452806024526000600060446000600087545af11561001b5760005260206000\
+                                     f3";
+
+/// Constructor of the bundled AMM pair, which stores the 4 words appended to it (token0, token1, reserve0
+/// and reserve1) in slots 0 to 3 and returns the runtime code before them.
+const PAIR_CONSTRUCTOR: &str =
+    "60808038036000396000516000556020516001556040516002556060516003556100c98061002d6000396000f3";
+
+/// Init code deploying the bundled ERC20.
+pub fn erc20_init_code() -> Bytes {
+    hex::decode(format!("{ERC20_CONSTRUCTOR}{ERC20_RUNTIME_CODE}")).unwrap().into()
+}
+
+pub fn erc20_runtime_code() -> Bytes {
+    hex::decode(ERC20_RUNTIME_CODE).unwrap().into()
+}
+
+/// Storage slot of an account's balance in the bundled ERC20, i.e. keccak256(account . 0).
+pub fn erc20_balance_slot(account: Address) -> B256 {
+    keccak256([account.into_word(), B256::ZERO].concat())
+}
+
+/// Init code deploying the bundled AMM pair between token0 and token1, starting with the given
+/// reserves. It doesn't hold them until they're sent to it separately (e.g. minted).
+pub fn pair_init_code(token0: Address, token1: Address, reserve0: U256, reserve1: U256) -> Bytes {
+    let mut init_code = hex::decode(format!("{PAIR_CONSTRUCTOR}{PAIR_RUNTIME_CODE}")).unwrap();
+    init_code.extend((token0, token1, reserve0, reserve1).abi_encode());
+    init_code.into()
+}
+
+pub fn pair_runtime_code() -> Bytes {
+    hex::decode(PAIR_RUNTIME_CODE).unwrap().into()
+}
+
+/// Storage of the bundled AMM pair between token0 and token1 with the given reserves.
+pub fn pair_storage(token0: Address, token1: Address, reserve0: U256, reserve1: U256) -> [(B256, B256); 4] {
+    [
+        (B256::with_last_byte(0), token0.into_word()),
+        (B256::with_last_byte(1), token1.into_word()),
+        (B256::with_last_byte(2), reserve0.into()),
+        (B256::with_last_byte(3), reserve1.into()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::sol_types::SolCall;
+    use alloy_evm::revm::context::result::ExecutionResult;
+    use alloy_evm::revm::context::TxEnv;
+    use alloy_evm::revm::database::{CacheDB, EmptyDB};
+    use alloy_evm::revm::primitives::TxKind;
+    use alloy_evm::revm::{Context, ExecuteCommitEvm, MainBuilder, MainContext};
+
+    use super::*;
+
+    /// The values of each PUSH2 in `code`, skipping over the immediates of other pushes.
+    fn push2_values(code: &[u8]) -> Vec<usize> {
+        let mut values = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let opcode = code[pc];
+            if opcode == 0x61 {
+                values.push(u16::from_be_bytes([code[pc + 1], code[pc + 2]]) as usize);
+            }
+            pc += 1 + if (0x60..=0x7f).contains(&opcode) { (opcode - 0x5f) as usize } else { 0 };
+        }
+        values
+    }
+
+    #[test]
+    fn constructors_copy_whole_runtime_code() {
+        for (constructor, runtime_code) in
+            [(ERC20_CONSTRUCTOR, erc20_runtime_code()), (PAIR_CONSTRUCTOR, pair_runtime_code())]
+        {
+            let constructor = hex::decode(constructor).unwrap();
+            // Each constructor pushes the runtime code's length, then its offset (i.e. the constructor's length).
+            assert_eq!(push2_values(&constructor), [runtime_code.len(), constructor.len()]);
+        }
+    }
+
+    /// An in-memory chain to deploy and call the bundled contracts on.
+    struct Chain {
+        evm: alloy_evm::revm::MainnetEvm<alloy_evm::revm::handler::MainnetContext<CacheDB<EmptyDB>>>,
+        nonces: std::collections::HashMap<Address, u64>,
+    }
+
+    impl Chain {
+        fn new() -> Self {
+            let evm = Context::mainnet().with_db(CacheDB::<EmptyDB>::default()).build_mainnet();
+            Self { evm, nonces: Default::default() }
+        }
+
+        fn transact(&mut self, caller: Address, kind: TxKind, data: impl Into<Bytes>) -> ExecutionResult {
+            let nonce = self.nonces.entry(caller).or_default();
+            let tx =
+                TxEnv { caller, kind, data: data.into(), gas_limit: 10_000_000, nonce: *nonce, ..Default::default() };
+            *nonce += 1;
+            self.evm.transact_commit(tx).unwrap()
+        }
+
+        fn deploy(&mut self, deployer: Address, init_code: Bytes) -> Address {
+            let address = deployer.create(self.nonces.get(&deployer).copied().unwrap_or_default());
+            assert!(self.transact(deployer, TxKind::Create, init_code).is_success());
+            address
+        }
+
+        /// Call `to`, returning its output, or None if it reverted.
+        fn call<C: SolCall>(&mut self, caller: Address, to: Address, call: C) -> Option<C::Return> {
+            let result = self.transact(caller, TxKind::Call(to), call.abi_encode());
+            result.is_success().then(|| C::abi_decode_returns(result.output().unwrap()).unwrap())
+        }
+
+        fn balance_of(&mut self, token: Address, account: Address) -> U256 {
+            self.call(Address::ZERO, token, ERC20::balanceOfCall { account }).unwrap()
+        }
+
+        fn storage(&self, address: Address, slot: B256) -> B256 {
+            let account = &self.evm.ctx.journaled_state.database.cache.accounts[&address];
+            account.storage.get(&slot.into()).copied().unwrap_or_default().into()
+        }
+    }
+
+    #[test]
+    fn erc20_transfers_approves_and_mints() {
+        let mut chain = Chain::new();
+        let owner = Address::repeat_byte(0xaa);
+        let [alice, bob, carol] = [1, 2, 3].map(Address::repeat_byte);
+        let token = chain.deploy(owner, erc20_init_code());
+        assert_eq!(chain.storage(token, ERC20_OWNER_SLOT), owner.into_word());
+
+        let mint = ERC20::mintCall { to: vec![alice, bob], amount: U256::from(1000) };
+        assert!(chain.call(alice, token, mint.clone()).is_none(), "only the owner can mint");
+        assert!(chain.call(owner, token, mint).is_some());
+        assert_eq!(chain.balance_of(token, alice), U256::from(1000));
+        assert_eq!(chain.storage(token, erc20_balance_slot(alice)), B256::from(U256::from(1000)));
+
+        assert!(chain.call(alice, token, ERC20::transferCall { to: carol, amount: U256::from(300) }).unwrap());
+        assert!(chain.call(alice, token, ERC20::transferCall { to: carol, amount: U256::from(701) }).is_none());
+        assert_eq!(chain.balance_of(token, alice), U256::from(700));
+        assert_eq!(chain.balance_of(token, carol), U256::from(300));
+
+        let transfer_from = ERC20::transferFromCall { from: bob, to: alice, amount: U256::from(100) };
+        let allowance = ERC20::allowanceCall { owner: bob, spender: carol };
+        assert!(chain.call(carol, token, transfer_from.clone()).is_none(), "carol has no allowance");
+        assert!(chain.call(bob, token, ERC20::approveCall { spender: carol, amount: U256::from(150) }).unwrap());
+        assert_eq!(chain.call(alice, token, allowance.clone()).unwrap(), U256::from(150));
+        assert!(chain.call(carol, token, transfer_from.clone()).unwrap());
+        assert!(chain.call(carol, token, transfer_from).is_none(), "carol only has 50 left");
+        assert_eq!(chain.call(alice, token, allowance).unwrap(), U256::from(50));
+        assert_eq!(chain.call(alice, token, ERC20::allowanceCall { owner: carol, spender: bob }).unwrap(), U256::ZERO);
+        assert_eq!(chain.balance_of(token, bob), U256::from(900));
+        assert_eq!(chain.balance_of(token, alice), U256::from(800));
+    }
+
+    #[test]
+    fn pair_swaps_both_ways() {
+        let mut chain = Chain::new();
+        let owner = Address::repeat_byte(0xaa);
+        let trader = Address::repeat_byte(1);
+        let (token0, token1) = (chain.deploy(owner, erc20_init_code()), chain.deploy(owner, erc20_init_code()));
+        let reserve = U256::from(1_000_000_000);
+        let pair = chain.deploy(owner, pair_init_code(token0, token1, reserve, reserve));
+        for (slot, value) in pair_storage(token0, token1, reserve, reserve) {
+            assert_eq!(chain.storage(pair, slot), value);
+        }
+
+        for token in [token0, token1] {
+            assert!(chain.call(owner, token, ERC20::mintCall { to: vec![pair], amount: reserve }).is_some());
+            assert!(chain.call(owner, token, ERC20::mintCall { to: vec![trader], amount: U256::from(1000) }).is_some());
+        }
+
+        let swap = Pair::swapCall { amountIn: U256::from(100), zeroForOne: true };
+        assert!(chain.call(trader, pair, swap.clone()).is_none(), "the pair isn't approved yet");
+        for token in [token0, token1] {
+            assert!(chain.call(trader, token, ERC20::approveCall { spender: pair, amount: U256::MAX }).unwrap());
+        }
+
+        // 100 * 997 * 1e9 / (1e9 * 1000 + 100 * 997) rounds down to 99.
+        assert_eq!(chain.call(trader, pair, swap).unwrap(), U256::from(99));
+        assert_eq!(chain.balance_of(token0, trader), U256::from(900));
+        assert_eq!(chain.balance_of(token1, trader), U256::from(1099));
+        let reserves = chain.call(trader, pair, Pair::getReservesCall {}).unwrap();
+        assert_eq!((reserves.reserve0, reserves.reserve1), (reserve + U256::from(100), reserve - U256::from(99)));
+        assert_eq!(chain.balance_of(token0, pair), reserves.reserve0);
+        assert_eq!(chain.balance_of(token1, pair), reserves.reserve1);
+
+        let amount_out =
+            chain.call(trader, pair, Pair::swapCall { amountIn: U256::from(99), zeroForOne: false }).unwrap();
+        // Swapping back loses another 0.3% fee, leaving the trader 2 token0 short.
+        assert_eq!(amount_out, U256::from(98));
+        assert_eq!(chain.balance_of(token0, trader), U256::from(998));
+        assert_eq!(chain.balance_of(token1, trader), U256::from(1000));
+
+        let dust = Pair::swapCall { amountIn: U256::from(1), zeroForOne: true };
+        assert!(chain.call(trader, pair, dust).is_none(), "swaps with no output revert");
+        let too_much = Pair::swapCall { amountIn: U256::from(5000), zeroForOne: true };
+        assert!(chain.call(trader, pair, too_much).is_none(), "the trader can't pay");
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::tx_queue::QueuedTx;
+
+// A corpus is a header followed by every tx back to back, all integers little endian:
+//
+//   header: magic (8 bytes) | chain id (u64) | tx count (u64)
+//   tx:     sender index (u32) | length (u32) | EIP-2718 encoded signed tx (length bytes)
+const MAGIC: &[u8; 8] = b"CRSCNDO1";
+const HEADER_LEN: usize = 24;
+const TX_HEADER_LEN: usize = 8;
+
+/// Writes signed txs to a corpus file, in the order they should be sent.
+pub struct CorpusWriter {
+    file: BufWriter<File>,
+    count: u64,
+}
+
+impl CorpusWriter {
+    pub fn create(path: &Path, chain_id: u64) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&chain_id.to_le_bytes())?;
+        file.write_all(&0u64.to_le_bytes())?; // Tx count, filled in by finish.
+        Ok(Self { file, count: 0 })
+    }
+
+    pub fn write_tx(&mut self, tx: &QueuedTx) -> io::Result<()> {
+        self.file.write_all(&tx.sender_index.to_le_bytes())?;
+        self.file.write_all(&(tx.raw.len() as u32).to_le_bytes())?;
+        self.file.write_all(&tx.raw)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Fill in the tx count and flush, returning the number of txs written.
+    pub fn finish(mut self) -> io::Result<u64> {
+        self.file.seek(SeekFrom::Start((HEADER_LEN - 8) as u64))?;
+        self.file.write_all(&self.count.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(self.count)
+    }
+}
+
+/// A memory-mapped corpus, whose txs are read straight from the page cache as they're streamed.
+pub struct Corpus {
+    mmap: Mmap,
+    chain_id: u64,
+    len: u64,
+}
+
+impl Corpus {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: The corpus must not be modified while mapped, same as any other input file.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[..8] != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a crescendo tx corpus"));
+        }
+
+        let chain_id = u64::from_le_bytes(mmap[8..16].try_into().unwrap());
+        let len = u64::from_le_bytes(mmap[16..24].try_into().unwrap());
+        Ok(Self { mmap, chain_id, len })
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Number of txs in the corpus, according to its header.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the corpus' txs as (sender index, encoded tx), stopping early if it's truncated.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        let mut remaining = &self.mmap[HEADER_LEN..];
+        std::iter::from_fn(move || {
+            let (tx_header, rest) = remaining.split_first_chunk::<TX_HEADER_LEN>()?;
+            let sender_index = u32::from_le_bytes(tx_header[..4].try_into().unwrap());
+            let len = u32::from_le_bytes(tx_header[4..].try_into().unwrap()) as usize;
+            let raw = rest.get(..len)?;
+            remaining = &rest[len..];
+            Some((sender_index, raw))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("crescendo-{}-{name}.bin", std::process::id()))
+    }
+
+    #[test]
+    fn round_trips_txs_in_order() {
+        let path = temp_path("round-trip");
+        let txs = [(7, vec![0x02, 0xaa, 0xbb]), (0, vec![]), (u32::MAX, vec![0x01; 300])];
+
+        let mut writer = CorpusWriter::create(&path, 1337).unwrap();
+        for (sender_index, raw) in &txs {
+            writer.write_tx(&QueuedTx { sender_index: *sender_index, raw: raw.clone() }).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), 3);
+
+        let corpus = Corpus::open(&path).unwrap();
+        assert_eq!(corpus.chain_id(), 1337);
+        assert_eq!(corpus.len(), 3);
+        let read: Vec<_> = corpus.iter().map(|(sender_index, raw)| (sender_index, raw.to_vec())).collect();
+        assert_eq!(read, txs);
+
+        // A truncated last tx is skipped, rather than read past the end.
+        drop(corpus);
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(Corpus::open(&path).unwrap().iter().count(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_corpora() {
+        let path = temp_path("not-a-corpus");
+        std::fs::write(&path, [0u8; 64]).unwrap();
+        assert_eq!(Corpus::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        std::fs::write(&path, MAGIC).unwrap();
+        assert_eq!(Corpus::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
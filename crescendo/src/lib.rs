@@ -0,0 +1,14 @@
+pub mod block_watcher;
+pub mod config;
+pub mod contracts;
+pub mod corpus;
+pub mod load_profile;
+pub mod metrics;
+pub mod network_stats;
+pub mod rate_controller;
+pub mod rpc;
+pub mod shutdown;
+pub mod summary;
+pub mod tx_queue;
+pub mod utils;
+pub mod workers;
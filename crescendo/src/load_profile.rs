@@ -0,0 +1,233 @@
+use std::f64::consts::TAU;
+use std::fmt;
+use std::time::Duration;
+
+use thousands::Separable;
+
+use crate::config::{self, LoadProfileConfig, LoadProfileEnd, LoadSegment};
+
+/// Where the run is along the load profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfilePoint {
+    pub pass: u64, // Times the profile has been played through before, when repeating.
+    pub segment_index: usize,
+    pub rate: u64,
+}
+
+impl LoadSegment {
+    fn duration_secs(&self) -> f64 {
+        match self {
+            LoadSegment::Hold { duration_secs, .. }
+            | LoadSegment::Ramp { duration_secs, .. }
+            | LoadSegment::Step { duration_secs, .. }
+            | LoadSegment::Spike { duration_secs, .. }
+            | LoadSegment::Sine { duration_secs, .. } => *duration_secs,
+        }
+    }
+
+    /// Panics unless the segment is_valid.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.duration_secs())
+    }
+
+    /// Whether every time in the segment is a positive duration (and a step segment has steps), as
+    /// `duration`, `rate_at` and formatting the segment assume.
+    pub fn is_valid(&self) -> bool {
+        let valid_secs = |secs: f64| Duration::try_from_secs_f64(secs).is_ok_and(|duration| !duration.is_zero());
+        valid_secs(self.duration_secs())
+            && match *self {
+                LoadSegment::Step { steps, .. } => steps > 0,
+                LoadSegment::Spike { spike_secs, .. } => valid_secs(spike_secs),
+                LoadSegment::Sine { period_secs, .. } => valid_secs(period_secs),
+                _ => true,
+            }
+    }
+
+    /// Rate `elapsed_secs` into the segment, in txs/s. Never zero, as the rate limiter can't be paused.
+    pub fn rate_at(&self, elapsed_secs: f64) -> u64 {
+        let progress = (elapsed_secs / self.duration().as_secs_f64()).clamp(0.0, 1.0);
+        let lerp = |from: u64, to: u64, t: f64| from as f64 + (to as f64 - from as f64) * t;
+
+        let rate = match *self {
+            LoadSegment::Hold { rate, .. } => rate as f64,
+            LoadSegment::Ramp { from, to, .. } => lerp(from, to, progress),
+            LoadSegment::Step { from, to, steps, .. } => {
+                let step = ((progress * steps as f64) as u32).min(steps - 1);
+                lerp(from, to, if steps > 1 { step as f64 / (steps - 1) as f64 } else { 1.0 })
+            }
+            LoadSegment::Spike { base, peak, spike_secs, .. } => {
+                if elapsed_secs < spike_secs {
+                    peak as f64
+                } else {
+                    base as f64
+                }
+            }
+            LoadSegment::Sine { mean, amplitude, period_secs, .. } => {
+                mean as f64 + amplitude as f64 * (TAU * elapsed_secs / period_secs).sin()
+            }
+        };
+
+        (rate.round() as u64).max(1)
+    }
+}
+
+impl fmt::Display for LoadSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let duration = self.duration();
+        match *self {
+            LoadSegment::Hold { rate, .. } => write!(f, "hold {}/s for {duration:?}", rate.separate_with_commas()),
+            LoadSegment::Ramp { from, to, .. } => {
+                write!(f, "ramp {}/s -> {}/s over {duration:?}", from.separate_with_commas(), to.separate_with_commas())
+            }
+            LoadSegment::Step { from, to, steps, .. } => write!(
+                f,
+                "step {}/s -> {}/s in {steps} steps over {duration:?}",
+                from.separate_with_commas(),
+                to.separate_with_commas()
+            ),
+            LoadSegment::Spike { base, peak, spike_secs, .. } => write!(
+                f,
+                "spike to {}/s for {:?}, then {}/s for the rest of {duration:?}",
+                peak.separate_with_commas(),
+                Duration::from_secs_f64(spike_secs),
+                base.separate_with_commas()
+            ),
+            LoadSegment::Sine { mean, amplitude, period_secs, .. } => write!(
+                f,
+                "sine {}/s ± {}/s every {:?} for {duration:?}",
+                mean.separate_with_commas(),
+                amplitude.separate_with_commas(),
+                Duration::from_secs_f64(period_secs)
+            ),
+        }
+    }
+}
+
+/// Duration of one pass through the load profile.
+pub fn profile_duration() -> Duration {
+    pass_duration(&config::get().load_profile)
+}
+
+fn pass_duration(config: &LoadProfileConfig) -> Duration {
+    config.segments.iter().map(LoadSegment::duration).sum()
+}
+
+/// Where the load profile is `elapsed` into the run, or None once it's
+/// over (which only happens if it's configured to stop the run at the end).
+pub fn profile_point(elapsed: Duration) -> Option<ProfilePoint> {
+    point_in(&config::get().load_profile, elapsed)
+}
+
+fn point_in(config: &LoadProfileConfig, elapsed: Duration) -> Option<ProfilePoint> {
+    let profile_secs = pass_duration(config).as_secs_f64();
+
+    let mut elapsed_secs = elapsed.as_secs_f64();
+    let mut pass = 0;
+    if elapsed_secs >= profile_secs {
+        match config.on_end {
+            LoadProfileEnd::Stop => return None,
+            LoadProfileEnd::Hold => {
+                let last_index = config.segments.len() - 1;
+                let last = &config.segments[last_index];
+                return Some(ProfilePoint {
+                    pass,
+                    segment_index: last_index,
+                    rate: last.rate_at(last.duration().as_secs_f64()),
+                });
+            }
+            LoadProfileEnd::Repeat => {
+                pass = (elapsed_secs / profile_secs) as u64;
+                elapsed_secs %= profile_secs;
+            }
+        }
+    }
+
+    for (segment_index, segment) in config.segments.iter().enumerate() {
+        let segment_secs = segment.duration().as_secs_f64();
+        if elapsed_secs < segment_secs || segment_index == config.segments.len() - 1 {
+            return Some(ProfilePoint { pass, segment_index, rate: segment.rate_at(elapsed_secs) });
+        }
+        elapsed_secs -= segment_secs;
+    }
+    unreachable!("load profile has no segments")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(on_end: LoadProfileEnd) -> LoadProfileConfig {
+        LoadProfileConfig {
+            update_interval_ms: 100,
+            on_end,
+            segments: vec![
+                LoadSegment::Ramp { from: 1_000, to: 5_000, duration_secs: 10.0 },
+                LoadSegment::Hold { rate: 5_000, duration_secs: 5.0 },
+                LoadSegment::Ramp { from: 5_000, to: 2_000, duration_secs: 10.0 },
+            ],
+        }
+    }
+
+    fn point(config: &LoadProfileConfig, elapsed_secs: f64) -> Option<(u64, usize, u64)> {
+        point_in(config, Duration::from_secs_f64(elapsed_secs))
+            .map(|point| (point.pass, point.segment_index, point.rate))
+    }
+
+    #[test]
+    fn ramps_interpolate_and_clamp() {
+        let ramp = LoadSegment::Ramp { from: 1_000, to: 5_000, duration_secs: 10.0 };
+        assert_eq!(ramp.rate_at(0.0), 1_000);
+        assert_eq!(ramp.rate_at(2.5), 2_000);
+        assert_eq!(ramp.rate_at(5.0), 3_000);
+        assert_eq!(ramp.rate_at(10.0), 5_000);
+        assert_eq!(ramp.rate_at(20.0), 5_000);
+
+        let down = LoadSegment::Ramp { from: 100, to: 0, duration_secs: 4.0 };
+        assert_eq!(down.rate_at(1.0), 75);
+        assert_eq!(down.rate_at(4.0), 1); // Never zero.
+    }
+
+    #[test]
+    fn other_segments_rate_at() {
+        let step = LoadSegment::Step { from: 1_000, to: 4_000, steps: 4, duration_secs: 8.0 };
+        let rates = [0.0, 1.9, 2.0, 4.0, 6.0, 8.0].map(|secs| step.rate_at(secs));
+        assert_eq!(rates, [1_000, 1_000, 2_000, 3_000, 4_000, 4_000]);
+        assert_eq!(LoadSegment::Step { from: 1_000, to: 4_000, steps: 1, duration_secs: 8.0 }.rate_at(0.0), 4_000);
+
+        let spike = LoadSegment::Spike { base: 1_000, peak: 9_000, spike_secs: 2.0, duration_secs: 10.0 };
+        assert_eq!([0.0, 1.9, 2.0, 9.0].map(|secs| spike.rate_at(secs)), [9_000, 9_000, 1_000, 1_000]);
+
+        let sine = LoadSegment::Sine { mean: 1_000, amplitude: 500, period_secs: 4.0, duration_secs: 8.0 };
+        assert_eq!([0.0, 1.0, 2.0, 3.0].map(|secs| sine.rate_at(secs)), [1_000, 1_500, 1_000, 500]);
+    }
+
+    #[test]
+    fn points_at_segment_boundaries() {
+        let config = profile(LoadProfileEnd::Stop);
+        assert_eq!(pass_duration(&config), Duration::from_secs(25));
+        assert_eq!(point(&config, 0.0), Some((0, 0, 1_000)));
+        assert_eq!(point(&config, 5.0), Some((0, 0, 3_000)));
+        // Each segment starts exactly when the previous one ends.
+        assert_eq!(point(&config, 10.0), Some((0, 1, 5_000)));
+        assert_eq!(point(&config, 14.9), Some((0, 1, 5_000)));
+        assert_eq!(point(&config, 15.0), Some((0, 2, 5_000)));
+        assert_eq!(point(&config, 20.0), Some((0, 2, 3_500)));
+    }
+
+    #[test]
+    fn points_after_the_last_segment() {
+        assert_eq!(point(&profile(LoadProfileEnd::Stop), 25.0), None);
+        assert_eq!(point(&profile(LoadProfileEnd::Stop), 100.0), None);
+
+        // Holding stays at the last segment's final rate.
+        let config = profile(LoadProfileEnd::Hold);
+        assert_eq!(point(&config, 25.0), Some((0, 2, 2_000)));
+        assert_eq!(point(&config, 1_000.0), Some((0, 2, 2_000)));
+
+        // Repeating starts the next pass from the first segment.
+        let config = profile(LoadProfileEnd::Repeat);
+        assert_eq!(point(&config, 25.0), Some((1, 0, 1_000)));
+        assert_eq!(point(&config, 37.0), Some((1, 1, 5_000)));
+        assert_eq!(point(&config, 55.0), Some((2, 0, 3_000)));
+    }
+}
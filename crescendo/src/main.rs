@@ -1,21 +1,20 @@
-use std::future::pending;
+use std::fs::File;
 use std::path::PathBuf;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use clap::Parser;
+use crescendo::block_watcher::BLOCK_WATCHER;
+use crescendo::config::{self, Config, RateLimitMode, TxSourceKind};
+use crescendo::corpus::Corpus;
+use crescendo::network_stats::NETWORK_STATS;
+use crescendo::summary::RunSummary;
+use crescendo::tx_queue::TX_QUEUE;
+use crescendo::workers::{self, DesireType, WorkerType};
+use crescendo::{metrics, shutdown, utils};
 use mimalloc::MiMalloc;
-
-mod config;
-mod network_stats;
-mod tx_queue;
-mod utils;
-mod workers;
-
-use crate::config::Config;
-use crate::network_stats::NETWORK_STATS;
-use crate::tx_queue::TX_QUEUE;
-use crate::workers::{DesireType, WorkerType};
+use thousands::Separable;
+use tokio::task::JoinSet;
 
 #[global_allocator]
 // Increases RPS by ~5.5% at the time of
@@ -38,11 +37,40 @@ async fn main() {
 
     let config_path = args.config_file.or(args.config).expect("[!] Config file path must be provided.");
     println!("[~] Loading config from {}...", config_path.display());
-    config::init(if config_path.exists() {
+    let config = if config_path.exists() {
         Config::from_file(&config_path).unwrap_or_else(|e| panic!("[!] Failed to load config file: {e:?}"))
     } else {
         panic!("[!] Config file not found: {}", config_path.display());
-    });
+    };
+    config.validate().unwrap_or_else(|e| panic!("[!] {e}"));
+    config::init(config);
+
+    let mut replay_file = None;
+    let corpus = match config::get().tx_source.kind {
+        TxSourceKind::Generate => None,
+        TxSourceKind::Replay => {
+            let path = config::get().tx_source.replay_path.as_ref().expect("[!] tx_source.replay_path must be set.");
+            replay_file = Some(
+                File::open(path).unwrap_or_else(|e| panic!("[!] Failed to open replay file {}: {e}", path.display())),
+            );
+            println!("[+] Opened replay file {} ({:?})", path.display(), config::get().tx_source.replay_format);
+            None
+        }
+        TxSourceKind::Corpus => {
+            let path = config::get().tx_source.corpus_path.as_ref().expect("[!] tx_source.corpus_path must be set.");
+            let corpus =
+                Corpus::open(path).unwrap_or_else(|e| panic!("[!] Failed to open corpus {}: {e}", path.display()));
+            println!("[+] Opened corpus of {} txs from {}", corpus.len().separate_with_commas(), path.display());
+            if corpus.chain_id() != config::get().tx_gen_worker.chain_id {
+                println!(
+                    "[!] Corpus was generated for chain id {}, not the configured {}.",
+                    corpus.chain_id(),
+                    config::get().tx_gen_worker.chain_id
+                );
+            }
+            Some(corpus)
+        }
+    };
 
     if let Err(err) = utils::increase_nofile_limit(config::get().network_worker.total_connections * 10) {
         println!("[!] Failed to increase file descriptor limit: {err}.");
@@ -60,10 +88,14 @@ async fn main() {
     // Given our desired breakdown of workers, translate this into actual numbers of workers to spawn.
     let (workers, worker_counts) = workers::assign_workers(
         core_ids, // Doesn't include the main runtime core.
-        vec![
-            (WorkerType::TxGen, DesireType::Percentage(config::get().workers.tx_gen_worker_percentage)),
-            (WorkerType::Network, DesireType::Percentage(config::get().workers.network_worker_percentage)),
-        ],
+        match config::get().tx_source.kind {
+            TxSourceKind::Generate => vec![
+                (WorkerType::TxGen, DesireType::Percentage(config::get().workers.tx_gen_worker_percentage)),
+                (WorkerType::Network, DesireType::Percentage(config::get().workers.network_worker_percentage)),
+            ],
+            // Txs are already signed (or re-signed by the feeder on the rayon pool), so every core can go to sending them.
+            _ => vec![(WorkerType::Network, DesireType::Percentage(1.0))],
+        },
         config::get().workers.thread_pinning, // Only log core ranges if thread pinning is actually enabled.
     );
 
@@ -73,46 +105,142 @@ async fn main() {
 
     // TODO: Having the assign_workers function do this would be cleaner.
     let mut tx_gen_worker_id = 0;
+    let num_tx_gen_workers = worker_counts.get(&WorkerType::TxGen).copied().unwrap_or(0) as u32;
     let mut network_worker_id = 0;
 
+    if config::get().tx_source.kind == TxSourceKind::Generate {
+        if config::get().bootstrap.enabled {
+            workers::run_bootstrap().await;
+        }
+        workers::init_workloads();
+    }
+
+    if config::get().tx_source.tracks_nonces() && config::get().tx_gen_worker.sync_nonces_on_startup {
+        println!("[*] Syncing nonces from {}...", config::get().network_worker.rpc_url());
+        if let Err(e) = workers::sync_nonces().await {
+            println!("[!] Failed to sync nonces from {}: {e}", config::get().network_worker.rpc_url());
+            println!("[!] Set tx_gen_worker.sync_nonces_on_startup = false to start every account from nonce 0.");
+            std::process::exit(1);
+        }
+        if let Err(e) = workers::sync_workloads().await {
+            println!("[!] Failed to sync workloads from {}: {e}", config::get().network_worker.rpc_url());
+            println!("[!] Set tx_gen_worker.sync_nonces_on_startup = false to skip syncing with the node.");
+            std::process::exit(1);
+        }
+    }
+
     println!("[*] Starting workers...");
+    let started_at = Instant::now();
+    let mut tx_gen_handles = Vec::new();
+    let mut network_handles = Vec::new();
 
     // Spawn the workers, pinning them to the appropriate cores if enabled.
     for (core_id, worker_type) in workers {
         match worker_type {
             WorkerType::TxGen => {
-                thread::spawn(move || {
+                tx_gen_handles.push(thread::spawn(move || {
                     utils::maybe_pin_thread(core_id);
-                    workers::tx_gen_worker(tx_gen_worker_id);
-                });
+                    workers::tx_gen_worker(tx_gen_worker_id, num_tx_gen_workers);
+                }));
                 tx_gen_worker_id += 1;
             }
             WorkerType::Network => {
-                thread::spawn(move || {
+                network_handles.push(thread::spawn(move || {
                     utils::maybe_pin_thread(core_id);
                     let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
 
                     rt.block_on(async {
+                        let mut connections = JoinSet::new();
                         for i in 0..connections_per_network_worker {
-                            tokio::spawn(workers::network_worker(
+                            connections.spawn(workers::network_worker(
                                 (network_worker_id * connections_per_network_worker + i) as usize,
                             ));
                         }
-                        pending::<()>().await; // Keep the runtime alive forever.
+                        connections.join_all().await; // Keep the runtime alive until shutdown.
                     });
-                });
+                }));
                 network_worker_id += 1;
             }
         }
     }
 
+    if let Some(corpus) = corpus {
+        // Unpinned, as it only copies txs from the page cache and is mostly asleep.
+        tx_gen_handles.push(thread::spawn(move || workers::corpus_feeder(corpus)));
+    }
+
+    if let Some(replay_file) = replay_file {
+        tx_gen_handles.push(thread::spawn(move || workers::replay_feeder(replay_file)));
+    }
+
     println!("[*] Starting reporters...");
 
+    if config::get().block_watcher.enabled {
+        tokio::spawn(BLOCK_WATCHER.start_watcher());
+        tokio::spawn(
+            BLOCK_WATCHER
+                .start_reporter(Duration::from_secs(config::get().reporters.block_watcher_report_interval_secs)),
+        );
+    }
+
+    if let Some(listen_addr) = config::get().reporters.prometheus_listen_addr {
+        tokio::spawn(metrics::start_exporter(listen_addr));
+    }
+
+    if config::get().rate_limiting.mode == RateLimitMode::Profile {
+        tokio::spawn(TX_QUEUE.start_load_profile(started_at));
+    }
+
     // Start reporters.
     tokio::spawn(TX_QUEUE.start_reporter(Duration::from_secs(config::get().reporters.tx_queue_report_interval_secs)));
     tokio::spawn(
         NETWORK_STATS.start_reporter(Duration::from_secs(config::get().reporters.network_stats_report_interval_secs)),
-    )
-    .await // Keep the main thread alive forever.
+    );
+
+    shutdown::wait_for_stop_condition(started_at).await;
+
+    println!("[*] Stopping tx generation...");
+    shutdown::stop_generating();
+    join_threads(tx_gen_handles).await;
+
+    if config::get().run.drain_queue_on_stop {
+        // Only txs still under max_total_txs can be drained, the rest would never be popped.
+        println!(
+            "[*] Draining {} queued txs (signal again to skip)...",
+            TX_QUEUE.sendable_len().separate_with_commas()
+        );
+        tokio::select! {
+            _ = async {
+                while TX_QUEUE.sendable_len() > 0 {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            } => {}
+            _ = shutdown::wait_for_signal() => println!("\n[!] Received shutdown signal, skipping drain..."),
+        }
+    }
+
+    println!("[*] Stopping network workers...");
+    shutdown::stop_sending();
+    join_threads(network_handles).await;
+
+    let summary = RunSummary::collect(started_at, TX_QUEUE.clear());
+    summary.print();
+    if let Some(path) = &config::get().run.summary_json_path {
+        match serde_json::to_string_pretty(&summary).map(|json| std::fs::write(path, json)) {
+            Ok(Ok(())) => println!("[+] Summary saved to: {}", path.display()),
+            Ok(Err(e)) => println!("[!] Failed to write summary to {}: {e}", path.display()),
+            Err(e) => println!("[!] Failed to serialize summary: {e}"),
+        }
+    }
+}
+
+/// Wait for all the given worker threads to exit, without blocking the main runtime.
+async fn join_threads(handles: Vec<thread::JoinHandle<()>>) {
+    tokio::task::spawn_blocking(move || {
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    })
+    .await
     .unwrap();
 }
@@ -0,0 +1,192 @@
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+
+use alloy::primitives::Bytes;
+use hdrhistogram::Histogram;
+use http_body_util::Full;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+use crate::block_watcher::BLOCK_WATCHER;
+use crate::config;
+use crate::network_stats::NETWORK_STATS;
+use crate::tx_queue::TX_QUEUE;
+
+/// Upper bounds (in seconds) of the buckets request latency histograms are exported with.
+const REQUEST_LATENCY_BUCKETS: &[f64] =
+    &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Upper bounds (in seconds) of the buckets inclusion latency histograms are exported with.
+const INCLUSION_LATENCY_BUCKETS: &[f64] = &[0.5, 1.0, 2.0, 3.0, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Serve Prometheus metrics at /metrics on the given address forever.
+pub async fn start_exporter(listen_addr: SocketAddr) {
+    let listener = TcpListener::bind(listen_addr)
+        .await
+        .unwrap_or_else(|e| panic!("[!] Failed to bind metrics exporter to {listen_addr}: {e}"));
+    println!("[*] Serving Prometheus metrics at http://{listen_addr}/metrics");
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("[!] Failed to accept metrics connection: {e}");
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service_fn(handle)).await {
+                eprintln!("[!] Failed to serve metrics connection: {e}");
+            }
+        });
+    }
+}
+
+async fn handle(req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    Ok(if req.uri().path() == "/metrics" {
+        Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Full::new(Bytes::from(render_metrics().into_bytes())))
+            .unwrap()
+    } else {
+        Response::builder().status(StatusCode::NOT_FOUND).body(Full::new(Bytes::new())).unwrap()
+    })
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+fn render_metrics() -> String {
+    let mut out = String::new();
+
+    write_metric(
+        &mut out,
+        "crescendo_txs_generated_total",
+        "counter",
+        "Txs pushed to the queue.",
+        TX_QUEUE.total_added(),
+    );
+    write_metric(
+        &mut out,
+        "crescendo_txs_popped_total",
+        "counter",
+        "Txs popped from the queue.",
+        TX_QUEUE.total_popped(),
+    );
+    write_metric(&mut out, "crescendo_tx_queue_length", "gauge", "Txs waiting in the queue.", TX_QUEUE.queue_len());
+    write_metric(&mut out, "crescendo_rate_limit", "gauge", "Current rate limit in txs/s.", TX_QUEUE.rate_limit());
+    if let Some(rate) = TX_QUEUE.max_sustainable_rate() {
+        write_metric(
+            &mut out,
+            "crescendo_max_sustainable_rate",
+            "gauge",
+            "Max txs/s sustained without missing any adaptive rate limiting target.",
+            rate,
+        );
+    }
+    write_metric(
+        &mut out,
+        "crescendo_txs_accepted_total",
+        "counter",
+        "Sent txs the node accepted, i.e. responded to without an error.",
+        NETWORK_STATS.total_requests(),
+    );
+
+    writeln!(out, "# HELP crescendo_tx_errors_total Txs that errored, by JSON-RPC code and normalized message.")
+        .unwrap();
+    writeln!(out, "# TYPE crescendo_tx_errors_total counter").unwrap();
+    for (bucket, count) in NETWORK_STATS.error_counts() {
+        let code = bucket.code.map(|code| code.to_string()).unwrap_or_default();
+        writeln!(
+            out,
+            "crescendo_tx_errors_total{{code=\"{code}\",message=\"{}\"}} {count}",
+            escape_label_value(&bucket.message)
+        )
+        .unwrap();
+    }
+
+    write_histogram(
+        &mut out,
+        "crescendo_request_latency_seconds",
+        "Round-trip time of requests to the node over the run, merged from every worker when scraped.",
+        &NETWORK_STATS.run_latencies(),
+        1e-6, // Recorded in microseconds.
+        REQUEST_LATENCY_BUCKETS,
+    );
+
+    if config::get().block_watcher.enabled {
+        write_metric(
+            &mut out,
+            "crescendo_txs_included_total",
+            "counter",
+            "Submitted txs seen in a block.",
+            BLOCK_WATCHER.total_included_txs(),
+        );
+        write_metric(
+            &mut out,
+            "crescendo_block_gas_total",
+            "counter",
+            "Gas used by blocks mined during the run.",
+            BLOCK_WATCHER.total_block_gas(),
+        );
+        write_metric(
+            &mut out,
+            "crescendo_txs_pending_inclusion",
+            "gauge",
+            "Submitted txs not yet seen in a block.",
+            BLOCK_WATCHER.pending_txs(),
+        );
+        write_metric(
+            &mut out,
+            "crescendo_txs_never_included_total",
+            "counter",
+            "Submitted txs not seen in a block before the inclusion timeout.",
+            BLOCK_WATCHER.total_never_included(),
+        );
+        write_histogram(
+            &mut out,
+            "crescendo_inclusion_latency_seconds",
+            "Time from submission to inclusion of txs over the run.",
+            &BLOCK_WATCHER.run_inclusion_latencies(),
+            1e-3, // Recorded in milliseconds.
+            INCLUSION_LATENCY_BUCKETS,
+        );
+    }
+
+    out
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} {kind}").unwrap();
+    writeln!(out, "{name} {value}").unwrap();
+}
+
+/// Write an hdr histogram as a Prometheus histogram with the given buckets
+/// (in seconds), scaling recorded values by `unit_secs` to convert them.
+fn write_histogram(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    histogram: &Histogram<u64>,
+    unit_secs: f64,
+    buckets: &[f64],
+) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} histogram").unwrap();
+    for upper_bound in buckets {
+        let count = histogram.count_between(0, (upper_bound / unit_secs) as u64);
+        writeln!(out, "{name}_bucket{{le=\"{upper_bound}\"}} {count}").unwrap();
+    }
+    writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", histogram.len()).unwrap();
+    // hdr histograms don't track an exact sum, but the mean is accurate to within the precision.
+    writeln!(out, "{name}_sum {}", histogram.mean() * histogram.len() as f64 * unit_secs).unwrap();
+    writeln!(out, "{name}_count {}", histogram.len()).unwrap();
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
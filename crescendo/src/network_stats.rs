@@ -1,39 +1,245 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
 use std::time::Duration;
 
 use crossbeam_utils::CachePadded;
+use dashmap::DashMap;
+use hdrhistogram::Histogram;
+use parking_lot::{Mutex, MutexGuard};
 use thousands::Separable;
 
 pub struct NetworkStats {
     requests: AtomicU64,
     errors: AtomicU64,
+    error_buckets: LazyLock<DashMap<ErrorBucket, u64>>,
+    latency_shards: Mutex<Vec<Arc<Mutex<Histogram<u64>>>>>,
+    run_latencies: LazyLock<Mutex<Histogram<u64>>>, // Merged from the shards whenever read.
+    rps_history: Mutex<Vec<u64>>,                   // RPS of every report interval so far.
+}
+
+thread_local! {
+    // Each network worker thread records request latencies into its own histogram, which only
+    // readers of the run's latencies ever contend on (briefly, once per interval) when merging it.
+    static LOCAL_LATENCIES: Arc<Mutex<Histogram<u64>>> = {
+        let shard = Arc::new(Mutex::new(new_latency_histogram()));
+        NETWORK_STATS.latency_shards.lock().push(shard.clone());
+        shard
+    };
+}
+
+/// Histogram of latencies in microseconds, from 1µs to 1 minute at 3 significant figures.
+pub fn new_latency_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, 60_000_000, 3).unwrap()
+}
+
+/// Format a histogram's percentiles, e.g. "p50: 1.2ms, p90: 3.4ms, ...".
+fn format_latency_percentiles(histogram: &Histogram<u64>) -> String {
+    let format_micros = |micros: u64| format!("{:.1?}", Duration::from_micros(micros));
+    [50.0, 90.0, 99.0, 99.9]
+        .iter()
+        .map(|percentile| format!("p{percentile}: {}", format_micros(histogram.value_at_percentile(*percentile))))
+        .chain(std::iter::once(format!("max: {}", format_micros(histogram.max()))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A class of error, keyed by JSON-RPC error code (if any) and normalized message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ErrorBucket {
+    pub code: Option<i64>, // None for transport-level errors.
+    pub message: Cow<'static, str>,
+}
+
+impl fmt::Display for ErrorBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} [{code}]", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Messages clients commonly return for rejected txs, most specific first, as
+/// the first one contained in an error message becomes its normalized form.
+const KNOWN_ERROR_MESSAGES: &[&str] = &[
+    "nonce too low",
+    "nonce too high",
+    "replacement transaction underpriced",
+    "transaction underpriced",
+    "already known",
+    "txpool is full",
+    "insufficient funds",
+    "intrinsic gas too low",
+    "exceeds block gas limit",
+    "max fee per gas less than block base fee",
+    "max priority fee per gas higher than max fee per gas",
+    "invalid chain id",
+    "invalid sender",
+    "oversized data",
+];
+
+pub const NONCE_TOO_LOW: &str = KNOWN_ERROR_MESSAGES[0];
+
+/// Normalize an error message into a bucket label, so errors that differ only in
+/// the hashes, addresses or amounts embedded in them are grouped together.
+pub fn normalize_error_message(message: &str) -> Cow<'static, str> {
+    // Checked without lowercasing first, as these are nearly all errors seen and it's per tx.
+    if let Some(known) = KNOWN_ERROR_MESSAGES.iter().find(|known| contains_ignore_ascii_case(message, known)) {
+        return Cow::Borrowed(known);
+    }
+
+    let lowercase = message.to_lowercase();
+    let normalized = lowercase
+        .split_whitespace()
+        .map(|word| {
+            let is_value = word.starts_with("0x") || word.chars().filter(char::is_ascii_digit).count() > 3;
+            if is_value {
+                "#"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Cow::Owned(normalized.chars().take(80).collect())
+}
+
+/// Whether `haystack` contains `needle` (lowercase ASCII), ignoring case.
+fn contains_ignore_ascii_case(haystack: &str, needle: &str) -> bool {
+    haystack.as_bytes().windows(needle.len()).any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Errors tallied by bucket while processing the responses to a batch, so recording them
+/// updates each distinct bucket once rather than contending on the shared map for every tx.
+#[derive(Debug, Default)]
+pub struct ErrorTally {
+    buckets: Vec<(ErrorBucket, u64)>,
+}
+
+impl ErrorTally {
+    /// Tally `count` errors, returning their normalized message.
+    pub fn add(&mut self, count: usize, code: Option<i64>, message: &str) -> &str {
+        let bucket = ErrorBucket { code, message: normalize_error_message(message) };
+        // A batch's errors are nearly always of a few kinds, so a linear scan beats hashing.
+        let index = match self.buckets.iter().position(|(tallied, _)| *tallied == bucket) {
+            Some(index) => index,
+            None => {
+                self.buckets.push((bucket, 0));
+                self.buckets.len() - 1
+            }
+        };
+        self.buckets[index].1 += count as u64;
+        &self.buckets[index].0.message
+    }
 }
 
 // Depending on build config, false sharing with this static and some
 // other frequently accessed memory can occur. To mitigate, we pad stats
 // to the length of a full cache line to avoid conflict. This is measured
 // to increase RPS by >10% in the release profile at the time of writing.
-pub static NETWORK_STATS: CachePadded<NetworkStats> =
-    CachePadded::new(NetworkStats { requests: AtomicU64::new(0), errors: AtomicU64::new(0) });
+pub static NETWORK_STATS: CachePadded<NetworkStats> = CachePadded::new(NetworkStats::new());
 
 impl NetworkStats {
+    const fn new() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            error_buckets: LazyLock::new(DashMap::new),
+            latency_shards: Mutex::new(Vec::new()),
+            run_latencies: LazyLock::new(|| Mutex::new(new_latency_histogram())),
+            rps_history: Mutex::new(Vec::new()),
+        }
+    }
+
     pub fn inc_requests_by(&self, count: usize) {
         self.requests.fetch_add(count as u64, Ordering::Relaxed);
     }
 
-    pub fn inc_errors_by(&self, count: usize) {
+    /// Record `count` errors, bucketed by code and normalized message.
+    pub fn inc_errors_by(&self, count: usize, code: Option<i64>, message: &str) {
         self.errors.fetch_add(count as u64, Ordering::Relaxed);
+        *self.error_buckets.entry(ErrorBucket { code, message: normalize_error_message(message) }).or_insert(0) +=
+            count as u64;
+    }
+
+    /// Record the errors tallied for a batch.
+    pub fn record_errors(&self, tally: ErrorTally) {
+        for (bucket, count) in tally.buckets {
+            self.errors.fetch_add(count, Ordering::Relaxed);
+            *self.error_buckets.entry(bucket).or_insert(0) += count;
+        }
+    }
+
+    /// Record the round-trip time of a request into the current thread's histogram.
+    pub fn record_latency(&self, latency: Duration) {
+        LOCAL_LATENCIES.with(|shard| shard.lock().saturating_record(latency.as_micros() as u64));
+    }
+
+    pub fn total_requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Snapshot of the total number of errors seen in each bucket.
+    pub fn error_counts(&self) -> HashMap<ErrorBucket, u64> {
+        self.error_buckets.iter().map(|entry| (entry.key().clone(), *entry.value())).collect()
+    }
+
+    /// Snapshot of all request latencies (in microseconds) recorded so far.
+    pub fn run_latencies(&self) -> Histogram<u64> {
+        self.merge_latency_shards().clone()
+    }
+
+    /// Latencies recorded since `snapshot` was taken, which is then updated to the
+    /// current run latencies, so each caller can track its own measurement interval.
+    pub fn latencies_since(&self, snapshot: &mut Histogram<u64>) -> Histogram<u64> {
+        let run_latencies = self.run_latencies();
+        let mut latencies = run_latencies.clone();
+        latencies.subtract(&*snapshot).unwrap(); // The run's latencies only ever grow.
+        *snapshot = run_latencies;
+        latencies
+    }
+
+    /// Peak RPS sustained over at least `window`, as measured by the reporter.
+    pub fn peak_sustained_rps(&self, window: Duration, measurement_interval: Duration) -> u64 {
+        let rps_history = self.rps_history.lock();
+        let intervals_per_window = (window.as_secs_f64() / measurement_interval.as_secs_f64()).ceil().max(1.0) as usize;
+        rps_history
+            .windows(intervals_per_window.min(rps_history.len()).max(1))
+            .map(|window| window.iter().sum::<u64>() / window.len() as u64)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Merge all threads' latencies recorded since the last merge into the run's latencies, resetting them.
+    fn merge_latency_shards(&self) -> MutexGuard<'_, Histogram<u64>> {
+        let mut run_latencies = self.run_latencies.lock();
+        for shard in self.latency_shards.lock().iter() {
+            let mut shard = shard.lock();
+            run_latencies.add(&*shard).unwrap();
+            shard.reset();
+        }
+        run_latencies
     }
 
     pub async fn start_reporter(&self, measurement_interval: Duration) {
         let mut last_requests = 0u64;
         let mut last_errors = 0u64;
+        let mut last_error_buckets: HashMap<ErrorBucket, u64> = HashMap::new();
+        let mut last_latencies = new_latency_histogram();
         let mut interval = tokio::time::interval(measurement_interval);
         interval.tick().await;
         loop {
             interval.tick().await;
-            let requests = self.requests.load(Ordering::Relaxed);
-            let errors = self.errors.load(Ordering::Relaxed);
+            let requests = self.total_requests();
+            let errors = self.total_errors();
             let rps = (requests - last_requests) / measurement_interval.as_secs();
             let eps = (errors - last_errors) / measurement_interval.as_secs();
             println!(
@@ -43,8 +249,95 @@ impl NetworkStats {
                 requests.separate_with_commas(),
                 errors.separate_with_commas()
             );
+
+            self.rps_history.lock().push(rps);
+
+            let interval_latencies = self.latencies_since(&mut last_latencies);
+            if !interval_latencies.is_empty() {
+                println!("[*] Latency {}", format_latency_percentiles(&interval_latencies));
+                println!("[*] Latency (run) {}", format_latency_percentiles(&last_latencies));
+            }
+
+            // Break down the errors seen this interval, most frequent first.
+            let error_buckets = self.error_counts();
+            let mut interval_errors: Vec<(&ErrorBucket, u64)> = error_buckets
+                .iter()
+                .map(|(bucket, total)| (bucket, total - last_error_buckets.get(bucket).copied().unwrap_or(0)))
+                .filter(|(_, count)| *count > 0)
+                .collect();
+            if !interval_errors.is_empty() {
+                interval_errors.sort_unstable_by_key(|(_, count)| std::cmp::Reverse(*count));
+                println!(
+                    "[!] Errors in the last {:?}: {}",
+                    measurement_interval,
+                    interval_errors
+                        .iter()
+                        .map(|(bucket, count)| format!("{bucket}: {}", count.separate_with_commas()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+
             last_requests = requests;
             last_errors = errors;
+            last_error_buckets = error_buckets;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_messages_are_bucketed_by_their_most_specific_form() {
+        assert_eq!(normalize_error_message("nonce too low: next nonce 5, tx nonce 3"), NONCE_TOO_LOW);
+        assert_eq!(normalize_error_message("Nonce Too Low"), NONCE_TOO_LOW);
+        assert_eq!(normalize_error_message("ALREADY KNOWN"), "already known");
+        assert_eq!(normalize_error_message("transaction underpriced"), "transaction underpriced");
+        assert_eq!(
+            normalize_error_message("replacement transaction underpriced"),
+            "replacement transaction underpriced"
+        );
+        assert_eq!(
+            normalize_error_message("insufficient funds for gas * price + value: balance 0, tx cost 21000"),
+            "insufficient funds"
+        );
+        assert!(matches!(normalize_error_message("txpool is full"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn unknown_messages_have_values_masked_and_are_truncated() {
+        assert_eq!(
+            normalize_error_message("Sender 0xabcdef is Blocked until block 123456"),
+            "sender # is blocked until block #"
+        );
+        // Values differing between txs land in the same bucket, short numbers are kept.
+        assert_eq!(
+            normalize_error_message("rejected by rule 42 at 0x1234"),
+            normalize_error_message("rejected by rule 42 at 0x5678")
+        );
+        assert_eq!(normalize_error_message(&"word ".repeat(40)).chars().count(), 80);
+    }
+
+    #[test]
+    fn tally_merges_errors_by_bucket() {
+        let mut tally = ErrorTally::default();
+        assert_eq!(tally.add(1, Some(-32000), "nonce too low: next nonce 5, tx nonce 3"), NONCE_TOO_LOW);
+        assert_eq!(tally.add(2, Some(-32000), "Nonce too low: next nonce 9, tx nonce 1"), NONCE_TOO_LOW);
+        tally.add(1, Some(-32003), "nonce too low");
+        tally.add(4, None, "request timed out");
+
+        let stats = NetworkStats::new();
+        stats.record_errors(tally);
+        stats.inc_errors_by(1, None, "request timed out");
+
+        assert_eq!(stats.total_errors(), 9);
+        let counts = stats.error_counts();
+        assert_eq!(counts.len(), 3);
+        let bucket = |code, message: &'static str| ErrorBucket { code, message: Cow::Borrowed(message) };
+        assert_eq!(counts[&bucket(Some(-32000), NONCE_TOO_LOW)], 3);
+        assert_eq!(counts[&bucket(Some(-32003), NONCE_TOO_LOW)], 1);
+        assert_eq!(counts[&bucket(None, "request timed out")], 5);
+    }
+}
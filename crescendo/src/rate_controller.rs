@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use thousands::Separable;
+
+use crate::block_watcher::{new_inclusion_histogram, BLOCK_WATCHER};
+use crate::config;
+use crate::network_stats::{new_latency_histogram, NETWORK_STATS};
+
+/// Max magnitude of the accumulated headroom, so the integral term can't wind up during long
+/// stretches where the rate limit is pinned (e.g. at max_ratelimit or by a node outage).
+const MAX_INTEGRAL: f64 = 5.0;
+
+/// If fewer txs than this fraction of the rate limit were popped in an interval,
+/// the rate limit isn't what's holding throughput back, so it isn't raised further.
+const MIN_LIMITER_UTILIZATION: f64 = 0.9;
+
+/// Closed-loop controller for the rate limit, driven by how close the node is to missing the
+/// configured targets (error rate, request latency p99 and optionally inclusion lag p99).
+///
+/// Every interval, the relative headroom to the most constrained target (1 - measured / target,
+/// negative once a target is missed) is fed through a PID loop, whose output scales the rate limit.
+pub struct AdaptiveRateController {
+    last_requests: u64,
+    last_errors: u64,
+    last_latencies: Histogram<u64>,
+    last_inclusion_latencies: Histogram<u64>,
+
+    integral: f64,
+    last_headroom: Option<f64>,
+
+    recent_rates: VecDeque<Option<u64>>, // Accepted txs/s of recent intervals, None if a target was missed.
+    max_sustainable_rate: Option<u64>,
+}
+
+impl Default for AdaptiveRateController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the node did over one interval, None where there was nothing to measure.
+struct IntervalMeasurement {
+    accepted_per_second: u64,
+    error_rate: Option<f64>,
+    latency_p99_ms: Option<f64>,
+    inclusion_lag_p99_ms: Option<f64>,
+}
+
+impl AdaptiveRateController {
+    pub fn new() -> Self {
+        Self {
+            last_requests: 0,
+            last_errors: 0,
+            last_latencies: new_latency_histogram(),
+            last_inclusion_latencies: new_inclusion_histogram(),
+            integral: 0.0,
+            last_headroom: None,
+            recent_rates: VecDeque::new(),
+            max_sustainable_rate: None,
+        }
+    }
+
+    /// Highest accepted txs/s sustained for sustain_intervals consecutive intervals without missing any target.
+    pub fn max_sustainable_rate(&self) -> Option<u64> {
+        self.max_sustainable_rate
+    }
+
+    /// Measure the interval just elapsed and compute the rate limit for the next one.
+    pub fn next_rate_limit(&mut self, rate_limit: u64, popped_per_second: u64, measurement_interval: Duration) -> u64 {
+        let config = &config::get().rate_limiting.adaptive;
+        let measurement = self.measure(measurement_interval);
+
+        // Relative headroom to each target, clamped so one badly missed target can't dominate the loop.
+        let headroom =
+            |measured: Option<f64>, target: f64| measured.map(|measured| (1.0 - measured / target).max(-1.0));
+        let headroom = [
+            headroom(measurement.error_rate, config.max_error_rate),
+            headroom(measurement.latency_p99_ms, config.max_latency_p99_ms as f64),
+            config
+                .max_inclusion_lag_p99_ms
+                .and_then(|target| headroom(measurement.inclusion_lag_p99_ms, target as f64)),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f64::min);
+
+        let Some(headroom) = headroom else {
+            // Nothing was sent or answered, so there's no signal to act on.
+            println!("[*] Adaptive rate limit: no requests answered in the last {measurement_interval:?}, holding");
+            return rate_limit;
+        };
+
+        self.record_interval(headroom >= 0.0, measurement.accepted_per_second, config.sustain_intervals);
+
+        let derivative = self.last_headroom.map_or(0.0, |last_headroom| headroom - last_headroom);
+        self.last_headroom = Some(headroom);
+
+        let limiter_bound = popped_per_second as f64 >= rate_limit as f64 * MIN_LIMITER_UTILIZATION;
+        let integral = (self.integral + headroom).clamp(-MAX_INTEGRAL, MAX_INTEGRAL);
+        let unclamped_factor = 1.0 + config.kp * headroom + config.ki * integral + config.kd * derivative;
+        let factor = unclamped_factor.clamp(1.0 / config.max_step_factor, config.max_step_factor);
+
+        let new_rate_limit = if factor > 1.0 && !limiter_bound {
+            // Raising the limit wouldn't increase throughput, and would just wind up the controller.
+            rate_limit
+        } else {
+            // Only integrate while the step isn't saturated, to avoid windup.
+            if factor == unclamped_factor {
+                self.integral = integral;
+            }
+            ((rate_limit as f64 * factor) as u64).clamp(config.min_ratelimit, config.max_ratelimit)
+        };
+
+        let format_millis = |millis: Option<f64>| millis.map_or("n/a".to_string(), |millis| format!("{millis:.1}ms"));
+        println!(
+            "[*] Adaptive rate limit: error rate: {}, p99: {}{}, headroom: {:+.2} -> {}/s{} (max sustainable: {})",
+            measurement.error_rate.map_or("n/a".to_string(), |rate| format!("{:.2}%", rate * 100.0)),
+            format_millis(measurement.latency_p99_ms),
+            if config.max_inclusion_lag_p99_ms.is_some() {
+                format!(", inclusion p99: {}", format_millis(measurement.inclusion_lag_p99_ms))
+            } else {
+                String::new()
+            },
+            headroom,
+            new_rate_limit.separate_with_commas(),
+            if limiter_bound { "" } else { " (not limiter bound)" },
+            self.max_sustainable_rate.map_or("n/a".to_string(), |rate| format!("{}/s", rate.separate_with_commas()))
+        );
+
+        new_rate_limit
+    }
+
+    fn measure(&mut self, measurement_interval: Duration) -> IntervalMeasurement {
+        let requests = NETWORK_STATS.total_requests();
+        let errors = NETWORK_STATS.total_errors();
+        let interval_requests = requests - self.last_requests;
+        let interval_errors = errors - self.last_errors;
+        self.last_requests = requests;
+        self.last_errors = errors;
+
+        let latencies = NETWORK_STATS.latencies_since(&mut self.last_latencies);
+        let inclusion_latencies = BLOCK_WATCHER.inclusion_latencies_since(&mut self.last_inclusion_latencies);
+
+        let sent = interval_requests + interval_errors;
+        IntervalMeasurement {
+            accepted_per_second: (interval_requests as f64 / measurement_interval.as_secs_f64()) as u64,
+            error_rate: (sent > 0).then(|| interval_errors as f64 / sent as f64),
+            // Latencies are recorded in microseconds.
+            latency_p99_ms: (!latencies.is_empty()).then(|| latencies.value_at_percentile(99.0) as f64 / 1e3),
+            // Only txs that made it into a block are measured, so this lags
+            // behind a node that stops including txs entirely.
+            inclusion_lag_p99_ms: (!inclusion_latencies.is_empty())
+                .then(|| inclusion_latencies.value_at_percentile(99.0) as f64),
+        }
+    }
+
+    /// Track the accepted rate of recent intervals, updating the max sustainable rate.
+    fn record_interval(&mut self, targets_met: bool, accepted_per_second: u64, sustain_intervals: usize) {
+        self.recent_rates.push_back(targets_met.then_some(accepted_per_second));
+        while self.recent_rates.len() > sustain_intervals {
+            self.recent_rates.pop_front();
+        }
+
+        if self.recent_rates.len() == sustain_intervals {
+            // The rate sustained over the window is the lowest of any interval in it.
+            if let Some(sustained) = self.recent_rates.iter().try_fold(u64::MAX, |min, rate| Some(min.min((*rate)?))) {
+                self.max_sustainable_rate = Some(self.max_sustainable_rate.map_or(sustained, |max| max.max(sustained)));
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use alloy::primitives::Bytes;
+use http::StatusCode;
+use http_body_util::{BodyExt, Full};
+use hyper::Request;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub type RpcResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRpcRequest<'a> {
+    pub jsonrpc: &'static str,
+    pub method: &'a str,
+    pub params: Value,
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonRpcResponse {
+    #[serde(default)]
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default)]
+    pub error: Option<JsonRpcError>,
+}
+
+/// The body of a response to a batch request. Nodes reply with a single
+/// (error) object instead of an array when rejecting the batch as a whole.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcBatchResponse {
+    Batch(Vec<JsonRpcResponse>),
+    Single(JsonRpcResponse),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Minimal JSON-RPC client for the (non hot path) calls crescendo
+/// makes to the node, e.g. nonce syncing. Cheap to clone.
+#[derive(Clone)]
+pub struct RpcClient {
+    client: Client<HttpConnector, Full<Bytes>>,
+    url: String,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        let client = Client::builder(TokioExecutor::new()).pool_idle_timeout(Duration::from_secs(90)).build({
+            let mut connector = HttpConnector::new();
+            connector.set_nodelay(true);
+            connector
+        });
+
+        Self { client, url: url.to_string() }
+    }
+
+    /// Send a single request, returning its deserialized result.
+    pub async fn request<T: DeserializeOwned>(&self, method: &str, params: Value) -> RpcResult<T> {
+        let body = serde_json::to_vec(&JsonRpcRequest { jsonrpc: "2.0", method, params, id: 1 })?;
+        let res: JsonRpcResponse = serde_json::from_slice(&self.post(body).await?)?;
+        match (res.result, res.error) {
+            (_, Some(err)) => Err(format!("RPC error {}: {}", err.code, err.message).into()),
+            (Some(result), None) => Ok(serde_json::from_value(result)?),
+            (None, None) => Err(format!("RPC response to {method} had neither a result nor an error").into()),
+        }
+    }
+
+    /// Send a batch of requests, returning the responses ordered to match `calls`.
+    pub async fn batch_request(&self, method: &str, calls: Vec<Value>) -> RpcResult<Vec<JsonRpcResponse>> {
+        let num_calls = calls.len();
+        let requests: Vec<_> = calls
+            .into_iter()
+            .enumerate()
+            .map(|(i, params)| JsonRpcRequest { jsonrpc: "2.0", method, params, id: i as u64 })
+            .collect();
+
+        let body = self.post(serde_json::to_vec(&requests)?).await?;
+        let mut responses = match serde_json::from_slice(&body)
+            .map_err(|e| format!("Malformed response to {method} batch: {e}"))?
+        {
+            JsonRpcBatchResponse::Batch(responses) => responses,
+            JsonRpcBatchResponse::Single(res) => {
+                return Err(match res.error {
+                    Some(err) => format!("{method} batch was rejected with RPC error {}: {}", err.code, err.message),
+                    None => format!("Expected an array of responses to {method} batch, got a single response"),
+                }
+                .into())
+            }
+        };
+        if responses.len() != num_calls {
+            return Err(format!("Expected {num_calls} responses to {method} batch, got {}", responses.len()).into());
+        }
+        // Responses to a batch may come back in any order.
+        responses.sort_unstable_by_key(|res| res.id);
+        Ok(responses)
+    }
+
+    async fn post(&self, body: Vec<u8>) -> RpcResult<hyper::body::Bytes> {
+        let req = Request::builder()
+            .method("POST")
+            .uri(&self.url)
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body)))?;
+
+        let res = self.client.request(req).await?;
+        if res.status() != StatusCode::OK {
+            return Err(format!("Request did not have OK status: {}", res.status()).into());
+        }
+        Ok(res.into_body().collect().await?.to_bytes())
+    }
+}
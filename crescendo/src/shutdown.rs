@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use thousands::Separable;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::config::{self, LoadProfileEnd, RateLimitMode};
+use crate::load_profile;
+use crate::tx_queue::TX_QUEUE;
+
+// Shutdown happens in two phases, so txs already generated
+// can optionally be drained before the network side stops.
+static STOP_GENERATING: AtomicBool = AtomicBool::new(false);
+static STOP_SENDING: AtomicBool = AtomicBool::new(false);
+
+// Set once a finite tx source (e.g. a corpus) has queued every tx it has.
+static SOURCE_EXHAUSTED: AtomicBool = AtomicBool::new(false);
+
+pub fn stop_generating() {
+    STOP_GENERATING.store(true, Ordering::Relaxed);
+}
+
+pub fn should_stop_generating() -> bool {
+    STOP_GENERATING.load(Ordering::Relaxed)
+}
+
+pub fn stop_sending() {
+    STOP_SENDING.store(true, Ordering::Relaxed);
+}
+
+pub fn should_stop_sending() -> bool {
+    STOP_SENDING.load(Ordering::Relaxed)
+}
+
+pub fn mark_source_exhausted() {
+    SOURCE_EXHAUSTED.store(true, Ordering::Relaxed);
+}
+
+/// Wait until the run should stop: SIGINT/SIGTERM, the configured duration elapsing, the configured
+/// number of txs having been sent, the load profile finishing, or a finite tx source running out.
+pub async fn wait_for_stop_condition(started_at: Instant) {
+    let config = &config::get().run;
+
+    let deadline = async {
+        match config.duration_secs {
+            Some(duration_secs) => {
+                tokio::time::sleep_until((started_at + Duration::from_secs(duration_secs)).into()).await
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    let max_txs_sent = async {
+        match config.max_total_txs {
+            Some(max_total_txs) => {
+                let mut interval = tokio::time::interval(Duration::from_millis(100));
+                while TX_QUEUE.total_popped() < max_total_txs {
+                    interval.tick().await;
+                }
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    let load_profile_over = async {
+        let rate_config = &config::get().rate_limiting;
+        if rate_config.mode == RateLimitMode::Profile && config::get().load_profile.on_end == LoadProfileEnd::Stop {
+            tokio::time::sleep_until((started_at + load_profile::profile_duration()).into()).await
+        } else {
+            std::future::pending().await
+        }
+    };
+
+    let source_sent = async {
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        while !SOURCE_EXHAUSTED.load(Ordering::Relaxed) || TX_QUEUE.queue_len() > 0 {
+            interval.tick().await;
+        }
+    };
+
+    tokio::select! {
+        _ = wait_for_signal() => println!("\n[!] Received shutdown signal, stopping..."),
+        _ = deadline => println!("[+] Reached run duration, stopping..."),
+        _ = max_txs_sent => println!("[+] Sent {} txs, stopping...", TX_QUEUE.total_popped().separate_with_commas()),
+        _ = load_profile_over => println!("[+] Load profile finished, stopping..."),
+        _ = source_sent => println!("[+] Sent every tx from the tx source, stopping..."),
+    }
+}
+
+/// Wait for SIGINT (Ctrl-C) or SIGTERM.
+pub async fn wait_for_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::{Duration, Instant};
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use thousands::Separable;
+
+use crate::block_watcher::BLOCK_WATCHER;
+use crate::config::{self, RateLimitMode};
+use crate::network_stats::NETWORK_STATS;
+use crate::tx_queue::TX_QUEUE;
+
+/// Window over which the peak RPS must be sustained to count.
+const PEAK_SUSTAINED_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub duration_secs: f64,
+    pub txs_generated: u64,
+    pub txs_sent: u64,
+    pub txs_accepted: u64,
+    pub txs_errored: u64,
+    pub txs_discarded: u64,
+    pub average_rps: f64,
+    pub peak_sustained_rps: u64,
+    pub latency_ms: LatencySummary,
+    pub errors: Vec<ErrorSummary>,
+    pub rate_limit_stages: Vec<RateLimitStage>,
+    pub max_sustainable_rps: Option<u64>, // Found by the adaptive rate controller.
+    pub inclusion: Option<InclusionSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub p99_9: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorSummary {
+    pub code: Option<i64>,
+    pub message: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateLimitStage {
+    pub at_secs: f64, // Since the start of the run.
+    pub rate_limit: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InclusionSummary {
+    pub txs_included: u64,
+    pub block_gas: u64, // Of all blocks mined during the run, not just our txs.
+    pub average_included_tps: f64,
+    pub txs_never_included: u64,
+    pub txs_still_pending: u64,
+    pub latency_ms: LatencySummary,
+}
+
+impl LatencySummary {
+    /// Summarize a histogram, scaling recorded values by `unit_ms` to convert them to milliseconds.
+    fn from_histogram(histogram: &Histogram<u64>, unit_ms: f64) -> Self {
+        let percentile = |p: f64| histogram.value_at_percentile(p) as f64 * unit_ms;
+        Self {
+            mean: histogram.mean() * unit_ms,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            p99_9: percentile(99.9),
+            max: histogram.max() as f64 * unit_ms,
+        }
+    }
+}
+
+impl RunSummary {
+    /// Collect the summary of a run that started at `started_at`, once all workers have stopped.
+    pub fn collect(started_at: Instant, txs_discarded: usize) -> Self {
+        let duration = started_at.elapsed();
+        let txs_accepted = NETWORK_STATS.total_requests();
+
+        let mut errors: Vec<ErrorSummary> = NETWORK_STATS
+            .error_counts()
+            .into_iter()
+            .map(|(bucket, count)| ErrorSummary { code: bucket.code, message: bucket.message.into_owned(), count })
+            .collect();
+        errors.sort_unstable_by_key(|error| std::cmp::Reverse(error.count));
+
+        let inclusion = config::get().block_watcher.enabled.then(|| InclusionSummary {
+            txs_included: BLOCK_WATCHER.total_included_txs(),
+            block_gas: BLOCK_WATCHER.total_block_gas(),
+            average_included_tps: BLOCK_WATCHER.total_included_txs() as f64 / duration.as_secs_f64(),
+            txs_never_included: BLOCK_WATCHER.total_never_included(),
+            txs_still_pending: BLOCK_WATCHER.pending_txs() as u64,
+            latency_ms: LatencySummary::from_histogram(&BLOCK_WATCHER.run_inclusion_latencies(), 1.0),
+        });
+
+        Self {
+            duration_secs: duration.as_secs_f64(),
+            txs_generated: TX_QUEUE.total_added(),
+            txs_sent: TX_QUEUE.total_popped(),
+            txs_accepted,
+            txs_errored: NETWORK_STATS.total_errors(),
+            txs_discarded: txs_discarded as u64,
+            average_rps: txs_accepted as f64 / duration.as_secs_f64(),
+            peak_sustained_rps: NETWORK_STATS.peak_sustained_rps(
+                PEAK_SUSTAINED_WINDOW,
+                Duration::from_secs(config::get().reporters.network_stats_report_interval_secs),
+            ),
+            latency_ms: LatencySummary::from_histogram(&NETWORK_STATS.run_latencies(), 1e-3),
+            errors,
+            rate_limit_stages: TX_QUEUE
+                .rate_limit_history()
+                .into_iter()
+                .map(|(set_at, rate_limit)| RateLimitStage {
+                    at_secs: set_at.saturating_duration_since(started_at).as_secs_f64(),
+                    rate_limit,
+                })
+                .collect(),
+            max_sustainable_rps: TX_QUEUE.max_sustainable_rate(),
+            inclusion,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("[+] Run summary ({:.1?}):", Duration::from_secs_f64(self.duration_secs));
+        println!(
+            "- Txs: {} generated, {} sent, {} accepted, {} errored, {} discarded",
+            self.txs_generated.separate_with_commas(),
+            self.txs_sent.separate_with_commas(),
+            self.txs_accepted.separate_with_commas(),
+            self.txs_errored.separate_with_commas(),
+            self.txs_discarded.separate_with_commas()
+        );
+        println!(
+            "- RPS: {} average, {} peak sustained over {:?}",
+            (self.average_rps as u64).separate_with_commas(),
+            self.peak_sustained_rps.separate_with_commas(),
+            PEAK_SUSTAINED_WINDOW
+        );
+        println!("- Latency: {}", self.latency_ms);
+        for error in &self.errors {
+            match error.code {
+                Some(code) => println!("- Error {} [{code}]: {}", error.message, error.count.separate_with_commas()),
+                None => println!("- Error {}: {}", error.message, error.count.separate_with_commas()),
+            }
+        }
+        match config::get().rate_limiting.mode {
+            RateLimitMode::Thresholds | RateLimitMode::Profile => println!(
+                "- Rate limit stages: {}",
+                self.rate_limit_stages
+                    .iter()
+                    .map(|stage| format!("{}/s @ {:.0}s", stage.rate_limit.separate_with_commas(), stage.at_secs))
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ),
+            // The adaptive rate controller adjusts the rate limit too often to list every stage.
+            RateLimitMode::Adaptive => {
+                let mut rate_limits = self.rate_limit_stages.iter().map(|stage| stage.rate_limit);
+                println!(
+                    "- Rate limit: {} adjustments between {}/s and {}/s, {}/s at stop",
+                    self.rate_limit_stages.len() - 1,
+                    rate_limits.clone().min().unwrap_or(0).separate_with_commas(),
+                    rate_limits.clone().max().unwrap_or(0).separate_with_commas(),
+                    rate_limits.next_back().unwrap_or(0).separate_with_commas()
+                );
+                match self.max_sustainable_rps {
+                    Some(rps) => println!("- Max sustainable throughput: {} txs/s", rps.separate_with_commas()),
+                    None => println!("- Max sustainable throughput: not found (targets never met for long enough)"),
+                }
+            }
+        }
+        if let Some(inclusion) = &self.inclusion {
+            println!(
+                "- Included: {} txs ({} TPS average, {} block gas), {} never included, {} still pending",
+                inclusion.txs_included.separate_with_commas(),
+                (inclusion.average_included_tps as u64).separate_with_commas(),
+                inclusion.block_gas.separate_with_commas(),
+                inclusion.txs_never_included.separate_with_commas(),
+                inclusion.txs_still_pending.separate_with_commas()
+            );
+            println!("- Inclusion latency: {}", inclusion.latency_ms);
+        }
+    }
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mean: {:.1}ms, p50: {:.1}ms, p90: {:.1}ms, p99: {:.1}ms, p99.9: {:.1}ms, max: {:.1}ms",
+            self.mean, self.p50, self.p90, self.p99, self.p99_9, self.max
+        )
+    }
+}
@@ -1,25 +1,42 @@
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use parking_lot::Mutex;
 use ratelimit::Ratelimiter;
 use thousands::Separable;
 
-use crate::config;
+use crate::config::{self, RateLimitMode};
+use crate::load_profile;
+use crate::rate_controller::AdaptiveRateController;
+
+/// A signed, encoded transaction waiting to be sent.
+pub struct QueuedTx {
+    pub sender_index: u32, // Index of the sending account in the signer list.
+    pub raw: Vec<u8>,
+}
 
 pub struct TxQueue {
     // TODO: RwLock? Natively concurrent deque?
-    queue: Mutex<VecDeque<Vec<u8>>>,
+    queue: Mutex<VecDeque<QueuedTx>>,
     total_added: AtomicU64,
     total_popped: AtomicU64,
+    max_total_txs: Option<u64>, // Txs popped past this are never handed out.
     rate_limiter: Ratelimiter,
+    rate_limit_history: Mutex<Vec<(Instant, u64)>>, // Every rate limit set, and when.
+    max_sustainable_rate: AtomicU64,                // 0 until found, adaptive mode only.
 }
 
 impl TxQueue {
     fn new() -> Self {
-        let initial_ratelimit = config::get().rate_limiting.initial_ratelimit;
+        let initial_ratelimit = match config::get().rate_limiting.mode {
+            RateLimitMode::Profile => load_profile::profile_point(Duration::ZERO).unwrap().rate,
+            _ => config::get().rate_limiting.initial_ratelimit,
+        };
+        Self::with_limits(initial_ratelimit, config::get().run.max_total_txs)
+    }
 
+    fn with_limits(initial_ratelimit: u64, max_total_txs: Option<u64>) -> Self {
         let rate_limiter = Ratelimiter::builder(initial_ratelimit, Duration::from_secs(1))
             .max_tokens(initial_ratelimit)
             .build()
@@ -29,7 +46,10 @@ impl TxQueue {
             queue: Mutex::new(VecDeque::new()),
             total_added: AtomicU64::new(0),
             total_popped: AtomicU64::new(0),
+            max_total_txs,
             rate_limiter,
+            rate_limit_history: Mutex::new(vec![(Instant::now(), initial_ratelimit)]),
+            max_sustainable_rate: AtomicU64::new(0),
         }
     }
 }
@@ -37,7 +57,7 @@ impl TxQueue {
 pub static TX_QUEUE: std::sync::LazyLock<TxQueue> = std::sync::LazyLock::new(TxQueue::new);
 
 impl TxQueue {
-    pub fn push_txs(&self, txs: Vec<Vec<u8>>) {
+    pub fn push_txs(&self, txs: Vec<QueuedTx>) {
         self.total_added.fetch_add(txs.len() as u64, Ordering::Relaxed);
         self.queue.lock().extend(txs);
     }
@@ -46,59 +66,172 @@ impl TxQueue {
         self.queue.lock().len()
     }
 
-    pub async fn pop_at_most(&self, max_count: usize) -> Option<Vec<Vec<u8>>> {
+    /// Queued txs that can still be popped, which stops short of the queue
+    /// length once popping all of them would go past max_total_txs.
+    pub fn sendable_len(&self) -> usize {
+        let queue = self.queue.lock();
+        queue.len().min(self.remaining_txs())
+    }
+
+    /// Txs that can be popped before reaching max_total_txs.
+    fn remaining_txs(&self) -> usize {
+        self.max_total_txs
+            .map_or(u64::MAX, |max| max.saturating_sub(self.total_popped()))
+            .try_into()
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Discard all queued txs, returning how many there were.
+    pub fn clear(&self) -> usize {
+        let mut queue = self.queue.lock();
+        let len = queue.len();
+        queue.clear();
+        len
+    }
+
+    pub fn total_added(&self) -> u64 {
+        self.total_added.load(Ordering::Relaxed)
+    }
+
+    pub fn total_popped(&self) -> u64 {
+        self.total_popped.load(Ordering::Relaxed)
+    }
+
+    /// The current rate limit, in txs/s.
+    pub fn rate_limit(&self) -> u64 {
+        self.rate_limiter.refill_amount()
+    }
+
+    /// Every rate limit set during the run, and when it was set.
+    pub fn rate_limit_history(&self) -> Vec<(Instant, u64)> {
+        self.rate_limit_history.lock().clone()
+    }
+
+    /// Highest throughput the adaptive rate controller has found the node to sustain, in txs/s.
+    pub fn max_sustainable_rate(&self) -> Option<u64> {
+        Some(self.max_sustainable_rate.load(Ordering::Relaxed)).filter(|rate| *rate > 0)
+    }
+
+    /// Set the rate limit, either draining the available tokens to prevent bursts (which stalls sending
+    /// until the next refill), or carrying them over for when the rate limit is adjusted frequently.
+    fn set_rate_limit(&self, new_rate_limit: u64, prevent_bursts: bool) {
+        let available = self.rate_limiter.available();
+
+        // The burst limit can never be below the refill amount, so update whichever keeps that true first.
+        if new_rate_limit >= self.rate_limit() {
+            self.rate_limiter.set_max_tokens(new_rate_limit).unwrap();
+            self.rate_limiter.set_refill_amount(new_rate_limit).unwrap();
+        } else {
+            self.rate_limiter.set_refill_amount(new_rate_limit).unwrap();
+            self.rate_limiter.set_max_tokens(new_rate_limit).unwrap();
+        }
+
+        // Raising the burst limit also fills the available tokens up to it.
+        self.rate_limiter.set_available(if prevent_bursts { 0 } else { available.min(new_rate_limit) }).unwrap();
+    }
+
+    /// Record the start of a new rate limiting stage, for the run summary.
+    fn record_rate_limit_stage(&self, rate_limit: u64) {
+        self.rate_limit_history.lock().push((Instant::now(), rate_limit));
+    }
+
+    pub async fn pop_at_most(&self, max_count: usize) -> Option<Vec<QueuedTx>> {
         // Assume the queue has sufficient items for now.
         let allowed = (0..max_count).take_while(|_| self.rate_limiter.try_wait().is_ok()).count();
         if allowed == 0 {
             return None;
         }
 
-        // Scope to release lock asap.
+        // Scope to release lock asap. Popped txs are counted under it, so max_total_txs can't be overshot.
         let drained = {
             let mut queue = self.queue.lock();
-            let to_drain = allowed.min(queue.len());
+            let to_drain = allowed.min(queue.len()).min(self.remaining_txs());
             if to_drain == 0 {
                 return None;
             }
+            self.total_popped.fetch_add(to_drain as u64, Ordering::Relaxed);
             queue.drain(..to_drain).collect::<Vec<_>>()
         };
 
-        self.total_popped.fetch_add(drained.len() as u64, Ordering::Relaxed);
-
         Some(drained)
     }
 
+    /// Move the rate limit along the configured load profile for the
+    /// run that started at `started_at`, until the profile is over.
+    pub async fn start_load_profile(&self, started_at: Instant) {
+        let config = &config::get().load_profile;
+        let mut interval = tokio::time::interval(Duration::from_millis(config.update_interval_ms));
+        let mut last_point = load_profile::profile_point(Duration::ZERO).unwrap();
+        println!("[+] Load profile segment 1/{}: {}", config.segments.len(), config.segments[0]);
+        loop {
+            interval.tick().await;
+            let Some(point) = load_profile::profile_point(started_at.elapsed()) else {
+                return; // The run is stopping.
+            };
+
+            if (point.pass, point.segment_index) != (last_point.pass, last_point.segment_index) {
+                println!(
+                    "[+] Load profile segment {}/{}: {}",
+                    point.segment_index + 1,
+                    config.segments.len(),
+                    config.segments[point.segment_index]
+                );
+                self.record_rate_limit_stage(point.rate);
+            }
+            if self.rate_limit() != point.rate {
+                self.set_rate_limit(point.rate, false); // Adjusted too often to drain tokens each time.
+            }
+            last_point = point;
+        }
+    }
+
     pub async fn start_reporter(&self, measurement_interval: std::time::Duration) {
         let mut last_total_added = 0u64;
         let mut last_total_popped = 0u64;
         let mut last_queue_len = 0usize;
+        let mut rate_controller = AdaptiveRateController::new();
         let mut interval = tokio::time::interval(measurement_interval);
         interval.tick().await;
         loop {
             interval.tick().await;
-            let total_added = self.total_added.load(Ordering::Relaxed);
-            let total_popped = self.total_popped.load(Ordering::Relaxed);
+            let total_added = self.total_added();
+            let total_popped = self.total_popped();
             let current_queue_len = self.queue_len();
             let added_per_second = (total_added - last_total_added) / measurement_interval.as_secs();
             let popped_per_second = (total_popped - last_total_popped) / measurement_interval.as_secs();
             let delta_per_second =
                 ((current_queue_len as i64 - last_queue_len as i64) as f64 / measurement_interval.as_secs_f64()) as i64;
 
-            // Adjust rate limit based on total popped transactions and thresholds.
             let rate_config = &config::get().rate_limiting;
-            let new_rate_limit = rate_config
-                .ratelimit_thresholds
-                .iter()
-                .rev()
-                .find(|(threshold, _)| total_popped >= (*threshold as u64))
-                .map(|(_, rate_limit)| *rate_limit)
-                .unwrap_or(rate_config.initial_ratelimit);
-
-            if self.rate_limiter.refill_amount() != new_rate_limit {
-                println!("[+] Adjusting rate limit to {} txs/s", new_rate_limit.separate_with_commas());
-                self.rate_limiter.set_max_tokens(new_rate_limit).unwrap(); // Burst limit must be set first.
-                self.rate_limiter.set_refill_amount(new_rate_limit).unwrap();
-                self.rate_limiter.set_available(0).unwrap(); // Prevent bursts.
+            match rate_config.mode {
+                RateLimitMode::Thresholds => {
+                    // Adjust rate limit based on total popped transactions and thresholds.
+                    let new_rate_limit = rate_config
+                        .ratelimit_thresholds
+                        .iter()
+                        .rev()
+                        .find(|(threshold, _)| total_popped >= (*threshold as u64))
+                        .map(|(_, rate_limit)| *rate_limit)
+                        .unwrap_or(rate_config.initial_ratelimit);
+
+                    if self.rate_limit() != new_rate_limit {
+                        println!("[+] Adjusting rate limit to {} txs/s", new_rate_limit.separate_with_commas());
+                        self.set_rate_limit(new_rate_limit, true); // Prevent bursts.
+                        self.record_rate_limit_stage(new_rate_limit);
+                    }
+                }
+                RateLimitMode::Adaptive => {
+                    let new_rate_limit =
+                        rate_controller.next_rate_limit(self.rate_limit(), popped_per_second, measurement_interval);
+                    if self.rate_limit() != new_rate_limit {
+                        self.set_rate_limit(new_rate_limit, false); // Adjusted too often to drain tokens each time.
+                        self.record_rate_limit_stage(new_rate_limit);
+                    }
+                    if let Some(rate) = rate_controller.max_sustainable_rate() {
+                        self.max_sustainable_rate.store(rate, Ordering::Relaxed);
+                    }
+                }
+                RateLimitMode::Profile => {} // Driven by start_load_profile instead.
             }
 
             println!(
@@ -107,7 +240,7 @@ impl TxQueue {
                 popped_per_second.separate_with_commas(),
                 delta_per_second.separate_with_commas(),
                 current_queue_len.separate_with_commas(),
-                self.rate_limiter.refill_amount().separate_with_commas()
+                self.rate_limit().separate_with_commas()
             );
 
             last_total_added = total_added;
@@ -116,3 +249,41 @@ impl TxQueue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A queue holding `count` txs, with its rate limiter full so only the cap limits popping.
+    fn queue_with_txs(count: u32, max_total_txs: Option<u64>) -> TxQueue {
+        let queue = TxQueue::with_limits(100, max_total_txs);
+        queue.rate_limiter.set_available(100).unwrap();
+        queue.push_txs((0..count).map(|sender_index| QueuedTx { sender_index, raw: vec![] }).collect());
+        queue
+    }
+
+    #[tokio::test]
+    async fn popping_stops_at_max_total_txs() {
+        let queue = queue_with_txs(10, Some(4));
+        assert_eq!(queue.sendable_len(), 4);
+
+        let popped = queue.pop_at_most(3).await.unwrap();
+        assert_eq!(popped.iter().map(|tx| tx.sender_index).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(queue.pop_at_most(3).await.unwrap().len(), 1);
+
+        // Txs left in the queue past the cap can't be drained, so a drain must stop waiting on them.
+        assert!(queue.pop_at_most(3).await.is_none());
+        assert_eq!(queue.total_popped(), 4);
+        assert_eq!(queue.queue_len(), 6);
+        assert_eq!(queue.sendable_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn uncapped_queue_drains_fully() {
+        let queue = queue_with_txs(10, None);
+        assert_eq!(queue.sendable_len(), 10);
+        assert_eq!(queue.pop_at_most(20).await.unwrap().len(), 10);
+        assert_eq!(queue.sendable_len(), 0);
+        assert!(queue.pop_at_most(20).await.is_none());
+    }
+}
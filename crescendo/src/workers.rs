@@ -4,9 +4,14 @@ use core_affinity::CoreId;
 
 mod network;
 mod tx_gen;
+mod tx_source;
 
 pub use network::network_worker;
-pub use tx_gen::tx_gen_worker;
+pub use tx_gen::{
+    build_tx, generate_tx, init_workloads, load_signers, request_nonce_resync, run_bootstrap, sign_and_encode_tx,
+    sync_nonces, sync_workloads, tx_gen_worker, TxGenerator,
+};
+pub use tx_source::{corpus_feeder, replay_feeder};
 
 use crate::utils::format_ranges;
 
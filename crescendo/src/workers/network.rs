@@ -1,107 +1,342 @@
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::time::{Duration, Instant};
 
-use alloy::primitives::{hex, Bytes};
-use http::StatusCode;
-use http_body_util::{BodyExt, Full};
-use hyper::Request;
-use hyper_util::client::legacy::connect::HttpConnector;
-use hyper_util::client::legacy::Client;
-use hyper_util::rt::TokioExecutor;
-use thousands::Separable;
+use alloy::primitives::{hex, B256};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
 
-use crate::config;
-use crate::network_stats::NETWORK_STATS;
-use crate::tx_queue::TX_QUEUE;
+use crate::block_watcher::BLOCK_WATCHER;
+use crate::config::Transport;
+use crate::network_stats::{ErrorTally, NETWORK_STATS, NONCE_TOO_LOW};
+use crate::rpc::{JsonRpcBatchResponse, JsonRpcResponse};
+use crate::tx_queue::{QueuedTx, TX_QUEUE};
+use crate::workers::request_nonce_resync;
+use crate::{config, shutdown};
+
+mod http;
+mod ipc;
+mod ws;
+
+/// How long a pipelined connection waits for outstanding responses once sending stops.
+const PIPELINE_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error txs are counted under when their request goes unanswered for request_timeout_ms.
+const REQUEST_TIMED_OUT: &str = "request timed out";
 
 pub async fn network_worker(worker_id: usize) {
+    match config::get().network_worker.transport {
+        Transport::Http => http::http_worker(worker_id).await,
+        Transport::Ws => ws::ws_worker(worker_id).await,
+        Transport::Ipc => ipc::ipc_worker(worker_id).await,
+    }
+}
+
+/// Encode a JSON-RPC batch of eth_sendRawTransaction requests, with sequential ids starting at `first_id`.
+fn encode_batch_request(txs: &[QueuedTx], first_id: u64) -> String {
+    format!(
+        "[{}]",
+        txs.iter()
+            .enumerate()
+            .map(|(i, tx)| {
+                format!(
+                    r#"{{"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x{}"],"id":{}}}"#,
+                    hex::encode(&tx.raw),
+                    first_id + i as u64
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+/// Record the outcome of a single tx from the response to it, tallying it into `errors` if it was
+/// rejected. Returns whether it was accepted.
+fn record_tx_response(res: JsonRpcResponse, sender_index: u32, sent_at: Instant, errors: &mut ErrorTally) -> bool {
+    match res.error {
+        Some(err) => {
+            let message = errors.add(1, Some(err.code), &err.message);
+            // Pre-signed txs can't be regenerated at a different nonce, so only resync ones we sign as we go.
+            if message == NONCE_TOO_LOW
+                && config::get().tx_gen_worker.resync_nonces_on_error
+                && config::get().tx_source.tracks_nonces()
+            {
+                request_nonce_resync(sender_index);
+            }
+            false
+        }
+        None => {
+            // The node replies with the tx's hash, which is what blocks list it by.
+            if config::get().block_watcher.enabled {
+                if let Some(hash) = res.result.as_ref().and_then(|result| result.as_str()?.parse::<B256>().ok()) {
+                    BLOCK_WATCHER.record_submitted(hash, sent_at);
+                }
+            }
+            true
+        }
+    }
+}
+
+/// A tx sent over a pipelined connection, awaiting its response.
+struct InFlightTx {
+    sender_index: u32,
+    sent_at: Instant,
+    request_id: u64, // Id of the first tx in its batch, which the request is keyed by.
+}
+
+/// A batch request sent over a pipelined connection, holding one of its max_in_flight_requests slots.
+struct InFlightRequest {
+    deadline: Instant,
+    unanswered: usize,
+}
+
+/// Send txs over a persistent connection that carries many batch requests at once (up to
+/// max_in_flight_requests), correlating responses back to their txs by id as they arrive.
+/// Requests not fully answered within request_timeout_ms free their slot, their unanswered
+/// txs being counted as errors (and any late responses to them ignored).
+///
+/// Returns once sending stops or the connection fails, counting any unanswered txs as errors.
+async fn run_pipelined_connection<W, R, E>(mut writer: W, mut reader: R)
+where
+    W: Sink<String> + Unpin,
+    W::Error: Display,
+    R: Stream<Item = Result<Vec<u8>, E>> + Unpin,
+    E: Display,
+{
     let config = &config::get().network_worker;
+    let mut in_flight: HashMap<u64, InFlightTx> = HashMap::new();
+    let mut in_flight_requests: HashMap<u64, InFlightRequest> = HashMap::new();
+    let mut next_id = 1;
+    let mut stopped_at: Option<Instant> = None;
 
-    let client: Client<_, Full<Bytes>> = Client::builder(TokioExecutor::new())
-        .pool_idle_timeout(Duration::from_secs(90))
-        .pool_max_idle_per_host(100)
-        .retry_canceled_requests(true)
-        .build({
-            let mut connector = HttpConnector::new();
-            connector.set_nodelay(true);
-            connector.set_keepalive(Some(Duration::from_secs(60)));
-            connector
-        });
-
-    loop {
-        if let Some(txs) = TX_QUEUE.pop_at_most(config.batch_factor).await {
-            let json_body = format!(
-                "[{}]",
-                txs.iter()
-                    .enumerate()
-                    .map(|(i, tx)| {
-                        format!(
-                            r#"{{"jsonrpc":"2.0","method":"eth_sendRawTransaction","params":["0x{}"],"id":{}}}"#,
-                            hex::encode(tx),
-                            i + 1
-                        )
-                    })
-                    .collect::<Vec<String>>()
-                    .join(",")
-            );
-
-            let req = Request::builder()
-                .method("POST")
-                .uri(&config.target_url)
-                .header("Content-Type", "application/json")
-                .body(Full::new(Bytes::from(json_body.into_bytes())))
-                .unwrap();
-
-            let start_time = Instant::now();
-            match client.request(req).await {
-                Ok(res) => {
-                    // Note: May be better to print for random workers, or a range, or the median + last + first.
-                    if worker_id == 0 {
-                        let duration = start_time.elapsed();
-                        let implied_total_rps =
-                            (txs.len() as f64 / duration.as_secs_f64()) * (config.total_connections as f64);
-                        println!(
-                            "[~] Worker {} request duration: {:.1?} ({} implied total RPS)",
-                            worker_id,
-                            duration,
-                            (implied_total_rps as u64).separate_with_commas()
-                        );
-                    }
+    let failure = loop {
+        expire_requests(&mut in_flight, &mut in_flight_requests);
 
-                    if res.status() == StatusCode::OK {
-                        match res.into_body().collect().await {
-                            Ok(collected) => {
-                                let body_bytes = collected.to_bytes();
-                                let body_str = std::str::from_utf8(&body_bytes).unwrap();
-
-                                let error_count = body_str.matches("\"error\":").count();
-                                if error_count > 0 {
-                                    println!("[!] RPC response ({}/{} errored): {}", error_count, txs.len(), body_str);
-                                    NETWORK_STATS.inc_errors_by(error_count);
-                                }
-
-                                NETWORK_STATS.inc_requests_by(txs.len() - error_count);
-                            }
-                            Err(e) => {
-                                eprintln!("[!] Failed to read response body: {e:?}");
-                                NETWORK_STATS.inc_errors_by(txs.len());
-                                tokio::time::sleep(Duration::from_millis(config.error_sleep_ms)).await;
-                            }
-                        }
-                    } else {
-                        println!("[!] Request did not have OK status: {res:?}");
-                        NETWORK_STATS.inc_errors_by(txs.len());
-                        tokio::time::sleep(Duration::from_millis(100)).await;
+        if shutdown::should_stop_sending() {
+            let stopped_at = *stopped_at.get_or_insert_with(Instant::now);
+            if in_flight.is_empty() || stopped_at.elapsed() > PIPELINE_DRAIN_TIMEOUT {
+                break None;
+            }
+        }
+        let can_send = stopped_at.is_none() && in_flight_requests.len() < config.max_in_flight_requests;
+
+        tokio::select! {
+            message = reader.next() => match message {
+                Some(Ok(body)) => {
+                    if let Err(e) = process_pipelined_response(&body, &mut in_flight, &mut in_flight_requests, next_id) {
+                        break Some(e);
                     }
                 }
-                Err(e) => {
-                    eprintln!("[!] Request failed: {e:?}");
-                    NETWORK_STATS.inc_errors_by(txs.len());
-                    tokio::time::sleep(Duration::from_millis(100)).await;
+                Some(Err(e)) => break Some(format!("connection error: {e}")),
+                None => break Some("connection closed".to_string()),
+            },
+            txs = pop_or_sleep(config.batch_factor), if can_send => {
+                let Some(txs) = txs else { continue };
+
+                let body = encode_batch_request(&txs, next_id);
+                let sent_at = Instant::now();
+                in_flight_requests.insert(next_id, InFlightRequest {
+                    deadline: sent_at + Duration::from_millis(config.request_timeout_ms),
+                    unanswered: txs.len(),
+                });
+                let request_id = next_id;
+                for tx in txs {
+                    in_flight.insert(next_id, InFlightTx { sender_index: tx.sender_index, sent_at, request_id });
+                    next_id += 1;
+                }
+
+                if let Err(e) = writer.send(body).await {
+                    break Some(format!("failed to send request: {e}"));
                 }
+            },
+            // Wake up periodically to notice shutdown and expired requests while waiting on responses.
+            _ = tokio::time::sleep(Duration::from_millis(100)), if !can_send => {}
+        }
+    };
+
+    if !in_flight.is_empty() {
+        NETWORK_STATS.inc_errors_by(in_flight.len(), None, failure.as_deref().unwrap_or("no response before shutdown"));
+    }
+}
+
+/// Drop requests past their deadline, counting their unanswered txs as errors.
+fn expire_requests(in_flight: &mut HashMap<u64, InFlightTx>, in_flight_requests: &mut HashMap<u64, InFlightRequest>) {
+    let now = Instant::now();
+    let num_requests = in_flight_requests.len();
+    in_flight_requests.retain(|_, request| request.deadline > now);
+    if in_flight_requests.len() == num_requests {
+        return;
+    }
+
+    let num_txs = in_flight.len();
+    in_flight.retain(|_, tx| in_flight_requests.contains_key(&tx.request_id));
+    NETWORK_STATS.inc_errors_by(num_txs - in_flight.len(), None, REQUEST_TIMED_OUT);
+}
+
+/// Pop up to `max_count` txs from the queue, or sleep for a bit while
+/// it repopulates if none are available (returning None afterwards).
+async fn pop_or_sleep(max_count: usize) -> Option<Vec<QueuedTx>> {
+    let txs = TX_QUEUE.pop_at_most(max_count).await;
+    if txs.is_none() {
+        tokio::time::sleep(Duration::from_millis(config::get().network_worker.tx_queue_empty_sleep_ms)).await;
+    }
+    txs
+}
+
+/// Process a response read from a pipelined connection, removing the txs it answers from `in_flight`
+/// (and their requests from `in_flight_requests`, once fully answered). `next_id` is the id the next
+/// tx sent will get, so responses with lower ids no longer in flight are late ones, and are ignored.
+///
+/// Errors if the response can't be correlated to the txs it's for, as the connection is then unusable.
+fn process_pipelined_response(
+    body: &[u8],
+    in_flight: &mut HashMap<u64, InFlightTx>,
+    in_flight_requests: &mut HashMap<u64, InFlightRequest>,
+    next_id: u64,
+) -> Result<(), String> {
+    let responses = match serde_json::from_slice::<JsonRpcBatchResponse>(body) {
+        Ok(JsonRpcBatchResponse::Batch(responses)) => responses,
+        Ok(JsonRpcBatchResponse::Single(res)) => vec![res],
+        Err(e) => return Err(format!("invalid response body: {e}")),
+    };
+
+    let mut latency_recorded = false;
+    let mut successes = 0;
+    let mut errors = ErrorTally::default();
+    let mut result = Ok(());
+    for res in responses {
+        let Some(id) = res.id else {
+            // An error the node couldn't attribute to a request (e.g. a batch it failed to parse), whose txs
+            // will time out. Record it on its own, rather than tearing down the connection over it.
+            let (code, message) =
+                res.error.map_or((None, "response without id".into()), |err| (Some(err.code), err.message));
+            errors.add(1, code, &format!("connection error: {message}"));
+            continue;
+        };
+        let Some(tx) = in_flight.remove(&id) else {
+            if id < next_id {
+                continue; // Already timed out (or a duplicate).
             }
-        } else {
-            // Sleep for a bit while the tx queue repopulates.
-            tokio::time::sleep(Duration::from_millis(config.tx_queue_empty_sleep_ms)).await;
+            result = Err(match res.error {
+                Some(err) => format!("uncorrelated error response: {}", err.message),
+                None => "uncorrelated response".to_string(),
+            });
+            break;
+        };
+
+        if let Some(request) = in_flight_requests.get_mut(&tx.request_id) {
+            request.unanswered -= 1;
+            if request.unanswered == 0 {
+                in_flight_requests.remove(&tx.request_id);
+            }
+        }
+
+        // All txs in a batch were sent together, so the first one's latency is the request's.
+        if !latency_recorded {
+            NETWORK_STATS.record_latency(tx.sent_at.elapsed());
+            latency_recorded = true;
         }
+        successes += record_tx_response(res, tx.sender_index, tx.sent_at, &mut errors) as usize;
+    }
+
+    NETWORK_STATS.record_errors(errors);
+    NETWORK_STATS.inc_requests_by(successes);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two in-flight batch requests of two txs each, with ids 1-2 and 3-4, expiring after `timeouts`.
+    fn in_flight_batches(timeouts: [Duration; 2]) -> (HashMap<u64, InFlightTx>, HashMap<u64, InFlightRequest>) {
+        config::init_default();
+        let sent_at = Instant::now();
+        let in_flight = (1..=4)
+            .map(|id| (id, InFlightTx { sender_index: id as u32, sent_at, request_id: if id <= 2 { 1 } else { 3 } }))
+            .collect();
+        let in_flight_requests = [(1, timeouts[0]), (3, timeouts[1])]
+            .into_iter()
+            .map(|(request_id, timeout)| (request_id, InFlightRequest { deadline: sent_at + timeout, unanswered: 2 }))
+            .collect();
+        (in_flight, in_flight_requests)
+    }
+
+    fn ids<V>(map: &HashMap<u64, V>) -> Vec<u64> {
+        let mut ids = map.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn responses_are_correlated_out_of_order() {
+        let (mut in_flight, mut in_flight_requests) = in_flight_batches([Duration::from_secs(60); 2]);
+
+        let body = br#"[{"jsonrpc":"2.0","result":"0x01","id":4},{"jsonrpc":"2.0","result":"0x02","id":1}]"#;
+        process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap();
+        assert_eq!(ids(&in_flight), [2, 3]);
+        assert_eq!(ids(&in_flight_requests), [1, 3]);
+        assert!(in_flight_requests.values().all(|request| request.unanswered == 1));
+
+        let body = br#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"already known"},"id":2}"#;
+        process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap();
+        assert_eq!(ids(&in_flight), [3]);
+        assert_eq!(ids(&in_flight_requests), [3]);
+
+        let body = br#"[{"jsonrpc":"2.0","result":"0x03","id":3}]"#;
+        process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap();
+        assert!(in_flight.is_empty() && in_flight_requests.is_empty());
+    }
+
+    #[test]
+    fn unknown_ids_fail_the_connection_unless_late() {
+        let (mut in_flight, mut in_flight_requests) = in_flight_batches([Duration::from_secs(60); 2]);
+        in_flight.remove(&2);
+
+        // A late response to a tx no longer in flight is ignored, without touching the rest.
+        let body = br#"[{"jsonrpc":"2.0","result":"0x02","id":2}]"#;
+        process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap();
+        assert_eq!(ids(&in_flight), [1, 3, 4]);
+
+        // One to an id never sent means responses can't be trusted to line up.
+        let body = br#"[{"jsonrpc":"2.0","result":"0x01","id":1},{"jsonrpc":"2.0","result":"0x09","id":9}]"#;
+        let err = process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap_err();
+        assert_eq!(err, "uncorrelated response");
+        assert_eq!(ids(&in_flight), [3, 4]);
+
+        let body = br#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"oops"},"id":5}"#;
+        let err = process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap_err();
+        assert_eq!(err, "uncorrelated error response: oops");
+
+        let err = process_pipelined_response(b"not json", &mut in_flight, &mut in_flight_requests, 5).unwrap_err();
+        assert!(err.starts_with("invalid response body"));
+    }
+
+    #[test]
+    fn null_id_errors_are_recorded_without_answering_txs() {
+        let (mut in_flight, mut in_flight_requests) = in_flight_batches([Duration::from_secs(60); 2]);
+
+        let body = br#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#;
+        process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap();
+        assert_eq!(ids(&in_flight), [1, 2, 3, 4]);
+        assert_eq!(ids(&in_flight_requests), [1, 3]);
+        assert!(NETWORK_STATS
+            .error_counts()
+            .keys()
+            .any(|bucket| bucket.to_string().contains("connection error: parse error")));
+    }
+
+    #[test]
+    fn expired_requests_drop_their_unanswered_txs() {
+        let (mut in_flight, mut in_flight_requests) = in_flight_batches([Duration::ZERO, Duration::from_secs(60)]);
+        in_flight.remove(&1);
+
+        expire_requests(&mut in_flight, &mut in_flight_requests);
+        assert_eq!(ids(&in_flight), [3, 4]);
+        assert_eq!(ids(&in_flight_requests), [3]);
+
+        // A response to an expired tx arriving afterwards is a late one.
+        let body = br#"[{"jsonrpc":"2.0","result":"0x02","id":2}]"#;
+        process_pipelined_response(body, &mut in_flight, &mut in_flight_requests, 5).unwrap();
+        assert_eq!(ids(&in_flight), [3, 4]);
     }
 }
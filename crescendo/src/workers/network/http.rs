@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use alloy::primitives::Bytes;
+use http::StatusCode;
+use http_body_util::{BodyExt, Full};
+use hyper::Request;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+use super::{encode_batch_request, record_tx_response, REQUEST_TIMED_OUT};
+use crate::network_stats::{ErrorTally, NETWORK_STATS};
+use crate::rpc::JsonRpcBatchResponse;
+use crate::tx_queue::{QueuedTx, TX_QUEUE};
+use crate::{config, shutdown};
+
+pub async fn http_worker(_worker_id: usize) {
+    let config = &config::get().network_worker;
+
+    let client: Client<_, Full<Bytes>> = Client::builder(TokioExecutor::new())
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(100)
+        .retry_canceled_requests(true)
+        .build({
+            let mut connector = HttpConnector::new();
+            connector.set_nodelay(true);
+            connector.set_keepalive(Some(Duration::from_secs(60)));
+            connector
+        });
+
+    while !shutdown::should_stop_sending() {
+        if let Some(txs) = TX_QUEUE.pop_at_most(config.batch_factor).await {
+            let json_body = encode_batch_request(&txs, 1);
+
+            let req = Request::builder()
+                .method("POST")
+                .uri(&config.target_url)
+                .header("Content-Type", "application/json")
+                .body(Full::new(Bytes::from(json_body.into_bytes())))
+                .unwrap();
+
+            // A hung node would otherwise block this worker (and shutdown) forever.
+            let start_time = Instant::now();
+            let deadline = tokio::time::Instant::from(start_time) + Duration::from_millis(config.request_timeout_ms);
+            match tokio::time::timeout_at(deadline, client.request(req)).await {
+                Ok(Ok(res)) => {
+                    if res.status() == StatusCode::OK {
+                        match tokio::time::timeout_at(deadline, res.into_body().collect()).await {
+                            Ok(Ok(collected)) => {
+                                NETWORK_STATS.record_latency(start_time.elapsed());
+                                process_response_body(&txs, &collected.to_bytes(), start_time);
+                            }
+                            Ok(Err(e)) => {
+                                NETWORK_STATS.inc_errors_by(
+                                    txs.len(),
+                                    None,
+                                    &format!("failed to read response body: {e}"),
+                                );
+                                tokio::time::sleep(Duration::from_millis(config.error_sleep_ms)).await;
+                            }
+                            Err(_) => NETWORK_STATS.inc_errors_by(txs.len(), None, REQUEST_TIMED_OUT),
+                        }
+                    } else {
+                        NETWORK_STATS.inc_errors_by(txs.len(), None, &format!("http status {}", res.status()));
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                }
+                Ok(Err(e)) => {
+                    NETWORK_STATS.inc_errors_by(txs.len(), None, &format!("request failed: {e}"));
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err(_) => NETWORK_STATS.inc_errors_by(txs.len(), None, REQUEST_TIMED_OUT),
+            }
+        } else {
+            // Sleep for a bit while the tx queue repopulates.
+            tokio::time::sleep(Duration::from_millis(config.tx_queue_empty_sleep_ms)).await;
+        }
+    }
+}
+
+/// Parse a batch response, correlating each entry back to the tx it was for by id,
+/// and record the outcome of every tx in the batch.
+fn process_response_body(txs: &[QueuedTx], body: &[u8], sent_at: Instant) {
+    let responses = match serde_json::from_slice::<JsonRpcBatchResponse>(body) {
+        Ok(JsonRpcBatchResponse::Batch(responses)) => responses,
+        Ok(JsonRpcBatchResponse::Single(res)) => {
+            // The batch was rejected as a whole, attribute the error to every tx in it.
+            let (code, message) = res
+                .error
+                .map_or((None, "unexpected single response to batch".into()), |err| (Some(err.code), err.message));
+            NETWORK_STATS.inc_errors_by(txs.len(), code, &message);
+            return;
+        }
+        Err(e) => {
+            NETWORK_STATS.inc_errors_by(txs.len(), None, &format!("invalid response body: {e}"));
+            return;
+        }
+    };
+
+    let mut answered = vec![false; txs.len()];
+    let mut successes = 0;
+    let mut errors = ErrorTally::default();
+    for res in responses {
+        // Request ids are 1-indexed positions in the batch.
+        let Some(index) = res.id.and_then(|id| id.checked_sub(1)).map(|i| i as usize).filter(|&i| i < txs.len()) else {
+            continue;
+        };
+        if std::mem::replace(&mut answered[index], true) {
+            continue; // Ignore duplicate responses for the same id.
+        }
+
+        successes += record_tx_response(res, txs[index].sender_index, sent_at, &mut errors) as usize;
+    }
+
+    let unanswered = answered.iter().filter(|answered| !**answered).count();
+    if unanswered > 0 {
+        errors.add(unanswered, None, "missing response");
+    }
+    NETWORK_STATS.record_errors(errors);
+    NETWORK_STATS.inc_requests_by(successes);
+}
@@ -0,0 +1,65 @@
+use std::io;
+use std::pin::pin;
+use std::time::Duration;
+
+use futures_util::{sink, stream};
+use serde::de::IgnoredAny;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::UnixStream;
+
+use super::run_pipelined_connection;
+use crate::{config, shutdown};
+
+pub async fn ipc_worker(worker_id: usize) {
+    let config = &config::get().network_worker;
+
+    while !shutdown::should_stop_sending() {
+        let (reader, writer) = match UnixStream::connect(&config.target_url).await {
+            Ok(stream) => stream.into_split(),
+            Err(e) => {
+                eprintln!("[!] Worker {worker_id} failed to connect to {}: {e}", config.target_url);
+                tokio::time::sleep(Duration::from_millis(config.error_sleep_ms)).await;
+                continue;
+            }
+        };
+
+        let writer = pin!(sink::unfold(writer, |mut writer, body: String| async move {
+            writer.write_all(body.as_bytes()).await?;
+            Ok::<_, io::Error>(writer)
+        }));
+        let reader = pin!(stream::unfold((reader, Vec::new()), |(mut reader, mut buf)| async move {
+            match read_message(&mut reader, &mut buf).await {
+                Ok(Some(message)) => Some((Ok(message), (reader, buf))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), (reader, buf))),
+            }
+        }));
+        run_pipelined_connection(writer, reader).await;
+
+        if !shutdown::should_stop_sending() {
+            // The connection failed, back off before reconnecting.
+            tokio::time::sleep(Duration::from_millis(config.error_sleep_ms)).await;
+        }
+    }
+}
+
+/// Read the next complete JSON value from the socket, as IPC responses are written back to back
+/// without any framing. Partial reads are buffered in `buf`. Returns None once the socket closes.
+async fn read_message(reader: &mut OwnedReadHalf, buf: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+    loop {
+        let mut values = serde_json::Deserializer::from_slice(buf).into_iter::<IgnoredAny>();
+        match values.next() {
+            Some(Ok(_)) => {
+                let end = values.byte_offset();
+                return Ok(Some(buf.drain(..end).collect()));
+            }
+            Some(Err(e)) if !e.is_eof() => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            _ => {} // Incomplete (or no) value buffered yet, keep reading.
+        }
+
+        if reader.read_buf(buf).await? == 0 {
+            return Ok(None);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::pin::pin;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::{Error, Message};
+
+use super::run_pipelined_connection;
+use crate::{config, shutdown};
+
+pub async fn ws_worker(worker_id: usize) {
+    let config = &config::get().network_worker;
+
+    while !shutdown::should_stop_sending() {
+        let stream = match connect_async(&config.target_url).await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("[!] Worker {worker_id} failed to connect to {}: {e}", config.target_url);
+                tokio::time::sleep(Duration::from_millis(config.error_sleep_ms)).await;
+                continue;
+            }
+        };
+
+        let (writer, reader) = stream.split();
+        let writer = pin!(writer.with(|body: String| async move { Ok::<_, Error>(Message::text(body)) }));
+        let reader = pin!(reader.filter_map(|message| async move {
+            match message {
+                Ok(Message::Text(text)) => Some(Ok(text.as_bytes().to_vec())),
+                Ok(Message::Binary(bytes)) => Some(Ok(bytes.to_vec())),
+                Ok(Message::Close(_)) => Some(Err(Error::ConnectionClosed)),
+                Ok(_) => None, // Pings are answered by tungstenite itself.
+                Err(e) => Some(Err(e)),
+            }
+        }));
+        run_pipelined_connection(writer, reader).await;
+
+        if !shutdown::should_stop_sending() {
+            // The connection failed, back off before reconnecting.
+            tokio::time::sleep(Duration::from_millis(config.error_sleep_ms)).await;
+        }
+    }
+}
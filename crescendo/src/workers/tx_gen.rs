@@ -1,20 +1,40 @@
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
 use std::sync::LazyLock;
 use std::time::Instant;
 
+use alloy::eips::eip2930::AccessList;
 use alloy::network::TxSignerSync;
-use alloy::primitives::{Address, TxKind, U256};
-use alloy::sol;
-use alloy::sol_types::SolCall;
-use alloy_consensus::{SignableTransaction, TxLegacy};
-use alloy_signer_local::coins_bip39::English;
-use alloy_signer_local::{MnemonicBuilder, PrivateKeySigner};
-use dashmap::DashMap;
-use rand::Rng;
-use rayon::prelude::*;
+use alloy::primitives::{Address, Bytes, Signature, TxKind, B256, U256, U64};
+use alloy::signers::SignerSync;
+use alloy_consensus::{SignableTransaction, TxEip1559, TxEip2930, TxLegacy, TypedTransaction};
+use alloy_signer_local::PrivateKeySigner;
+use dashmap::{DashMap, DashSet};
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::{Rng, RngCore};
+use secp256k1::{Message, Secp256k1, SecretKey, SignOnly};
 use thousands::Separable;
 
-use crate::config;
-use crate::tx_queue::TX_QUEUE;
+use crate::config::{SigningBackend, WorkloadConfig, WorkloadKind};
+use crate::rpc::{RpcClient, RpcResult};
+use crate::tx_queue::{QueuedTx, TX_QUEUE};
+use crate::{config, shutdown};
+
+mod blob;
+mod bootstrap;
+mod contract;
+mod distribution;
+mod erc20;
+mod keys;
+mod native;
+mod set_code;
+mod swap;
+
+pub use bootstrap::run_bootstrap;
+use distribution::AccountSampler;
+pub use keys::load_signers;
 
 static NONCE_MAP: LazyLock<DashMap<u32, u64>> = LazyLock::new(|| {
     let map = DashMap::with_capacity(config::get().tx_gen_worker.num_accounts as usize);
@@ -26,64 +46,177 @@ static NONCE_MAP: LazyLock<DashMap<u32, u64>> = LazyLock::new(|| {
 
 static SIGNER_LIST: LazyLock<Vec<PrivateKeySigner>> = LazyLock::new(|| {
     let start = Instant::now();
-    let config = &config::get().tx_gen_worker;
-    let list: Vec<PrivateKeySigner> = (0..config.num_accounts)
-        .into_par_iter()
-        .map(|i| MnemonicBuilder::<English>::default().phrase(&config.mnemonic).index(i).unwrap().build().unwrap())
-        .collect();
+    let list = load_signers(&config::get().tx_gen_worker);
     let duration = start.elapsed();
-    println!("[+] Initalized signer list of length {} in {:.1?}", config.num_accounts.separate_with_commas(), duration);
+    println!("[+] Initalized signer list of length {} in {:.1?}", list.len().separate_with_commas(), duration);
     list
 });
 
-sol! {
-    interface ERC20 {
-        function transfer(address to, uint256 amount) external returns (bool);
+/// Pickers of senders among the accounts no workload reserves, and recipients among the first
+/// 1/recipient_distribution_factor.
+static SENDERS: LazyLock<AccountSampler> = LazyLock::new(|| {
+    let config = &config::get().tx_gen_worker;
+    AccountSampler::new(
+        "sender_distribution",
+        &config.sender_distribution,
+        unreserved_accounts(),
+        unreserved_accounts(),
+    )
+});
+static RECIPIENTS: LazyLock<AccountSampler> =
+    LazyLock::new(|| new_recipient_sampler(config::get().tx_gen_worker.recipient_distribution_factor));
+
+/// The signer list's keys, for signing with libsecp256k1 instead of k256.
+static SECP256K1_KEYS: LazyLock<Vec<SecretKey>> = LazyLock::new(|| {
+    SIGNER_LIST.iter().map(|signer| SecretKey::from_byte_array(&signer.to_bytes().0).unwrap()).collect()
+});
+
+/// Accounts with a nonce resync currently in flight, to avoid piling up
+/// duplicate requests when many of an account's txs fail at once.
+static PENDING_RESYNCS: LazyLock<DashSet<u32>> = LazyLock::new(DashSet::new);
+
+/// Generators for the configured workloads, and their weights to pick one for each tx by.
+static WORKLOADS: LazyLock<Workloads> = LazyLock::new(|| {
+    let workloads = &config::get().workloads;
+    // Each workload's reserved accounts follow the previous one's, after the unreserved ones.
+    let mut next_reserved = unreserved_accounts();
+    Workloads {
+        generators: workloads
+            .iter()
+            .map(|workload| {
+                let reserved = next_reserved..next_reserved + reserved_accounts(workload);
+                next_reserved = reserved.end;
+                new_tx_generator(workload, reserved)
+            })
+            .collect(),
+        weights: WeightedIndex::new(workloads.iter().map(|workload| workload.weight))
+            .unwrap_or_else(|e| panic!("[!] Invalid workload weights: {e}")),
     }
+});
+
+struct Workloads {
+    generators: Vec<Box<dyn TxGenerator>>,
+    weights: WeightedIndex<f64>,
 }
 
-pub fn tx_gen_worker(_worker_id: u32) {
-    let config = &config::get().tx_gen_worker;
+/// Builds the unsigned txs of one workload.
+pub trait TxGenerator: Send + Sync {
+    /// Pick the index of the account to send the next tx from, by sender_distribution unless overridden.
+    fn sample_sender(&self, rng: &mut dyn RngCore) -> u32 {
+        SENDERS.sample(rng)
+    }
 
-    let mut rng = rand::rng();
-    let mut tx_batch = Vec::with_capacity(config.batch_size as usize);
+    /// Build a tx to be sent by `sender` at `nonce`.
+    fn build_tx(&self, rng: &mut dyn RngCore, sender: Address, nonce: u64) -> TypedTransaction;
 
-    loop {
-        let account_index = rng.random_range(0..config.num_accounts); // Acount we'll be sending from.
-
-        // Get and increment nonce atomically.
-        let nonce = {
-            let mut entry = NONCE_MAP.get_mut(&account_index).unwrap();
-            let current_nonce = *entry;
-            *entry = current_nonce + 1;
-            current_nonce
-        };
-
-        let (signer, recipient) = (
-            &SIGNER_LIST[account_index as usize],
-            SIGNER_LIST[rng.random_range(0..(config.num_accounts / config.recipient_distribution_factor)) as usize] // Send to 1/Nth of the accounts.
-                .address(),
-        );
+    /// Pick up state an earlier run against the same chain left on the node (e.g. approvals already sent),
+    /// once nonces are synced. Nothing to pick up by default.
+    fn sync_with_node<'a>(&'a self, _rpc: &'a RpcClient) -> Pin<Box<dyn Future<Output = RpcResult<()>> + 'a>> {
+        Box::pin(async { Ok(()) })
+    }
+}
 
-        let tx = sign_and_encode_tx(
-            signer,
-            TxLegacy {
-                chain_id: Some(config.chain_id),
-                nonce,
-                gas_price: config.gas_price as u128,
-                gas_limit: config.gas_limit,
-                to: TxKind::Call(config.token_contract_address.parse::<Address>().unwrap()),
-                value: U256::ZERO,
-                input: ERC20::transferCall {
-                    to: recipient,
-                    amount: U256::from(rng.random_range(1..=config.max_transfer_amount)),
-                }
-                .abi_encode()
-                .into(),
-            },
+/// Number of accounts at the end of the list a workload reserves for its own use, which no other txs are sent from.
+fn reserved_accounts(workload: &WorkloadConfig) -> u32 {
+    match workload.kind {
+        WorkloadKind::Blob { accounts, .. } => accounts.unwrap_or(0),
+        WorkloadKind::SetCode { authorities, .. } => authorities.unwrap_or(set_code::DEFAULT_NUM_AUTHORITIES),
+        _ => 0,
+    }
+}
+
+/// Number of accounts before those reserved by workloads, which the rest send from.
+fn unreserved_accounts() -> u32 {
+    let config = config::get();
+    let reserved: u64 = config.workloads.iter().map(|workload| reserved_accounts(workload) as u64).sum();
+    if reserved >= config.tx_gen_worker.num_accounts as u64 {
+        panic!(
+            "[!] Workloads reserve {} accounts, leaving none of the {} for the others to send from.",
+            reserved.separate_with_commas(),
+            config.tx_gen_worker.num_accounts.separate_with_commas()
         );
+    }
+    config.tx_gen_worker.num_accounts - reserved as u32
+}
 
-        tx_batch.push(tx);
+/// Build a workload's generator, given the range of accounts reserved for it (empty if it reserves none).
+fn new_tx_generator(workload: &WorkloadConfig, reserved: Range<u32>) -> Box<dyn TxGenerator> {
+    let gas_limit = workload.gas_limit.unwrap_or(config::get().tx_gen_worker.gas_limit);
+    match &workload.kind {
+        WorkloadKind::Erc20Transfer => Box::new(erc20::Erc20Transfer::new(gas_limit)),
+        &WorkloadKind::NativeTransfer { min_value, max_value, recipient_distribution_factor } => {
+            Box::new(native::NativeTransfer::new(
+                workload.gas_limit.unwrap_or(native::NATIVE_TRANSFER_GAS),
+                min_value,
+                max_value,
+                recipient_distribution_factor.unwrap_or(config::get().tx_gen_worker.recipient_distribution_factor),
+            ))
+        }
+        WorkloadKind::ContractDeploy { init_code } => {
+            Box::new(contract::ContractDeploy::new(gas_limit, init_code.as_deref()))
+        }
+        WorkloadKind::ContractCall { to, input, random_words } => {
+            Box::new(contract::ContractCall::new(gas_limit, to, input.as_deref(), random_words.unwrap_or(0)))
+        }
+        WorkloadKind::Blob { accounts, blobs_per_tx, data, num_payloads, max_fee_per_blob_gas } => {
+            let mixed = config::get().workloads.iter().any(|other| !matches!(other.kind, WorkloadKind::Blob { .. }));
+            if mixed && accounts.is_none() {
+                panic!("[!] blob workloads mixed with other kinds must set accounts to send blob txs from.");
+            }
+            Box::new(blob::BlobTx::new(
+                workload.gas_limit.unwrap_or(native::NATIVE_TRANSFER_GAS),
+                accounts.map(|_| reserved),
+                blobs_per_tx.unwrap_or(1),
+                data.as_deref(),
+                num_payloads.unwrap_or(blob::DEFAULT_NUM_PAYLOADS),
+                max_fee_per_blob_gas.unwrap_or(blob::DEFAULT_MAX_FEE_PER_BLOB_GAS),
+            ))
+        }
+        WorkloadKind::SetCode { delegate, authorizations_per_tx, call_input, .. } => Box::new(set_code::SetCode::new(
+            workload.gas_limit,
+            delegate,
+            reserved,
+            authorizations_per_tx.unwrap_or(1),
+            call_input.as_deref(),
+        )),
+        &WorkloadKind::Swap { ref pair, ref token1, min_amount_in, max_amount_in } => {
+            Box::new(swap::Swap::new(gas_limit, pair.as_deref(), token1.as_deref(), min_amount_in, max_amount_in))
+        }
+    }
+}
+
+/// Build the workload generators, printing the mix. Panics if any workload is misconfigured.
+pub fn init_workloads() {
+    LazyLock::force(&SENDERS);
+    LazyLock::force(&RECIPIENTS);
+    LazyLock::force(&WORKLOADS);
+
+    let workloads = &config::get().workloads;
+    let total_weight: f64 = workloads.iter().map(|workload| workload.weight).sum();
+    let mix: Vec<String> = workloads
+        .iter()
+        .map(|workload| format!("{:.1}% {}", workload.weight / total_weight * 100.0, workload.kind.name()))
+        .collect();
+    println!("[*] Workload mix: {}", mix.join(", "));
+}
+
+thread_local! {
+    // Resyncs are spawned from network worker runtimes, so give each its own client.
+    static RESYNC_CLIENT: RpcClient = RpcClient::new(config::get().network_worker.rpc_url());
+
+    // Creating a context precomputes its signing tables, so each tx gen thread does so once.
+    static SECP256K1_CONTEXT: Secp256k1<SignOnly> = Secp256k1::signing_only();
+}
+
+pub fn tx_gen_worker(worker_id: u32, num_workers: u32) {
+    let config = &config::get().tx_gen_worker;
+    distribution::set_worker_partition(worker_id, num_workers);
+
+    let mut rng = rand::rng();
+    let mut tx_batch = Vec::with_capacity(config.batch_size as usize);
+
+    while !shutdown::should_stop_generating() {
+        tx_batch.push(generate_tx(&mut rng));
 
         // Once we've accumulated batch_size transactions, drain them all to the queue.
         if tx_batch.len() >= config.batch_size as usize {
@@ -92,11 +225,309 @@ pub fn tx_gen_worker(_worker_id: u32) {
     }
 }
 
-pub fn sign_and_encode_tx(signer: &PrivateKeySigner, mut tx: TxLegacy) -> Vec<u8> {
-    // TODO: Upstream to alloy the ability to use the secp256k1
-    // crate instead of k256 for this which is like 5x+ faster.
-    let signature = signer.sign_transaction_sync(&mut tx).unwrap();
+/// Generate a signed tx from a random account, using (and incrementing) its next nonce.
+pub fn generate_tx(rng: &mut impl Rng) -> QueuedTx {
+    let (account_index, tx) = build_tx(rng);
+    QueuedTx { sender_index: account_index, raw: sign_and_encode_tx(account_index, tx) }
+}
+
+/// Build an unsigned tx of a randomly picked workload from a sender picked by sender_distribution (or the
+/// workload's own accounts), using (and incrementing) its next nonce. Returns the index of the sending
+/// account along with it.
+pub fn build_tx(rng: &mut impl Rng) -> (u32, TypedTransaction) {
+    let workload = &WORKLOADS.generators[WORKLOADS.weights.sample(rng)];
+
+    let account_index = workload.sample_sender(rng); // Acount we'll be sending from.
+    let nonce = next_nonce(account_index);
+    (account_index, workload.build_tx(rng, SIGNER_LIST[account_index as usize].address(), nonce))
+}
+
+/// Picker of recipients by recipient_distribution among the first 1/`distribution_factor` accounts.
+fn new_recipient_sampler(distribution_factor: u32) -> AccountSampler {
+    let config = &config::get().tx_gen_worker;
+    AccountSampler::new(
+        "recipient_distribution",
+        &config.recipient_distribution,
+        config.num_accounts / distribution_factor,
+        unreserved_accounts(),
+    )
+}
+
+/// Pick a random recipient with `recipients`, e.g. RECIPIENTS.
+fn random_recipient(rng: &mut dyn RngCore, recipients: &AccountSampler) -> Address {
+    SIGNER_LIST[recipients.sample(rng) as usize].address()
+}
+
+/// Build a tx with the configured fees, rolling for its type so a single run can mix legacy and dynamic-fee txs.
+fn build_typed_tx(
+    rng: &mut dyn RngCore,
+    nonce: u64,
+    gas_limit: u64,
+    to: TxKind,
+    value: U256,
+    input: Bytes,
+) -> TypedTransaction {
+    build_typed_tx_with_access_list(rng, nonce, gas_limit, to, value, input, AccessList::default())
+}
+
+/// Like build_typed_tx, but with an access list, which legacy txs can't carry so EIP-2930 txs are sent instead.
+fn build_typed_tx_with_access_list(
+    rng: &mut dyn RngCore,
+    nonce: u64,
+    gas_limit: u64,
+    to: TxKind,
+    value: U256,
+    input: Bytes,
+    access_list: AccessList,
+) -> TypedTransaction {
+    let config = &config::get().tx_gen_worker;
+
+    if rng.random_bool(config.eip1559_fraction) {
+        TypedTransaction::Eip1559(TxEip1559 {
+            chain_id: config.chain_id,
+            nonce,
+            gas_limit,
+            max_fee_per_gas: config.max_fee_per_gas as u128,
+            max_priority_fee_per_gas: config.max_priority_fee_per_gas as u128,
+            to,
+            value,
+            access_list,
+            input,
+        })
+    } else if !access_list.is_empty() {
+        TypedTransaction::Eip2930(TxEip2930 {
+            chain_id: config.chain_id,
+            nonce,
+            gas_price: config.gas_price as u128,
+            gas_limit,
+            to,
+            value,
+            access_list,
+            input,
+        })
+    } else {
+        TypedTransaction::Legacy(TxLegacy {
+            chain_id: Some(config.chain_id),
+            nonce,
+            gas_price: config.gas_price as u128,
+            gas_limit,
+            to,
+            value,
+            input,
+        })
+    }
+}
+
+/// Get and increment an account's nonce atomically.
+pub fn next_nonce(account_index: u32) -> u64 {
+    let mut entry = NONCE_MAP.get_mut(&account_index).unwrap();
+    let current_nonce = *entry;
+    *entry = current_nonce + 1;
+    current_nonce
+}
+
+/// Sign a tx as the account at `account_index` in the signer list, and encode it for sending.
+pub fn sign_and_encode_tx(account_index: u32, mut tx: TypedTransaction) -> Vec<u8> {
+    let signature = match config::get().tx_gen_worker.signing_backend {
+        SigningBackend::K256 => SIGNER_LIST[account_index as usize].sign_transaction_sync(&mut tx).unwrap(),
+        SigningBackend::Secp256k1 => sign_hash_secp256k1(&SECP256K1_KEYS[account_index as usize], tx.signature_hash()),
+    };
+    encode_signed_tx(tx, signature)
+}
+
+fn encode_signed_tx(tx: TypedTransaction, signature: Signature) -> Vec<u8> {
     let mut payload = Vec::new();
     tx.into_signed(signature).eip2718_encode(&mut payload);
     payload
 }
+
+/// Sign a hash as the account at `account_index` with the configured backend, e.g. for an authorization.
+fn sign_hash(account_index: u32, hash: B256) -> Signature {
+    match config::get().tx_gen_worker.signing_backend {
+        SigningBackend::K256 => SIGNER_LIST[account_index as usize].sign_hash_sync(&hash).unwrap(),
+        SigningBackend::Secp256k1 => sign_hash_secp256k1(&SECP256K1_KEYS[account_index as usize], hash),
+    }
+}
+
+/// Sign a hash with libsecp256k1, which is ~5x faster than k256. Both use RFC6979 nonces
+/// and normalize to low-s, so the signature (and thus the encoded tx) is byte-identical.
+fn sign_hash_secp256k1(key: &SecretKey, hash: B256) -> Signature {
+    let message = Message::from_digest(hash.0);
+    let (recovery_id, bytes) =
+        SECP256K1_CONTEXT.with(|secp| secp.sign_ecdsa_recoverable(&message, key)).serialize_compact();
+    Signature::from_bytes_and_parity(&bytes, i32::from(recovery_id) != 0)
+}
+
+/// Fetch the pending nonce of every account from the target node, so runs
+/// can pick up where a previous run against the same chain left off.
+pub async fn sync_nonces() -> RpcResult<()> {
+    let start = Instant::now();
+    let config = &config::get().tx_gen_worker;
+    let rpc = RpcClient::new(config::get().network_worker.rpc_url());
+
+    let account_indices: Vec<u32> = (0..config.num_accounts).collect();
+    let mut batches = account_indices.chunks(config.nonce_sync_batch_size).map(|chunk| chunk.to_vec());
+    let mut in_flight = tokio::task::JoinSet::new();
+    let mut total_synced = 0u64;
+
+    loop {
+        // Keep up to nonce_sync_concurrency batch requests in flight at a time.
+        while in_flight.len() < config.nonce_sync_concurrency {
+            let Some(batch) = batches.next() else { break };
+            let rpc = rpc.clone();
+            in_flight.spawn(async move {
+                let nonces = fetch_pending_nonces(&rpc, &batch).await;
+                (batch, nonces)
+            });
+        }
+
+        let Some(joined) = in_flight.join_next().await else { break };
+        let (batch, nonces) = joined.unwrap();
+        for (account_index, nonce) in batch.into_iter().zip(nonces?) {
+            total_synced += nonce;
+            NONCE_MAP.insert(account_index, nonce);
+        }
+    }
+
+    println!(
+        "[+] Synced nonces for {} accounts in {:.1?} ({} txs already sent)",
+        config.num_accounts.separate_with_commas(),
+        start.elapsed(),
+        total_synced.separate_with_commas()
+    );
+    Ok(())
+}
+
+/// Let each workload pick up where a previous run against the same chain left off, after sync_nonces.
+pub async fn sync_workloads() -> RpcResult<()> {
+    let rpc = RpcClient::new(config::get().network_worker.rpc_url());
+    for generator in &WORKLOADS.generators {
+        generator.sync_with_node(&rpc).await?;
+    }
+    Ok(())
+}
+
+/// Resync an account's nonce in the background after the node reported its tx nonce too low,
+/// moving it forward to the node's pending nonce. No-op if a resync is already in flight.
+pub fn request_nonce_resync(account_index: u32) {
+    if !PENDING_RESYNCS.insert(account_index) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let rpc = RESYNC_CLIENT.with(|rpc| rpc.clone());
+        match fetch_pending_nonces(&rpc, &[account_index]).await {
+            Ok(nonces) => {
+                // Txs generated since the failed one may already have a higher nonce, never move backwards.
+                let mut entry = NONCE_MAP.get_mut(&account_index).unwrap();
+                *entry = (*entry).max(nonces[0]);
+            }
+            Err(e) => eprintln!("[!] Failed to resync nonce for account {account_index}: {e}"),
+        }
+        PENDING_RESYNCS.remove(&account_index);
+    });
+}
+
+async fn fetch_pending_nonces(rpc: &RpcClient, account_indices: &[u32]) -> RpcResult<Vec<u64>> {
+    let calls =
+        account_indices.iter().map(|&i| serde_json::json!([SIGNER_LIST[i as usize].address(), "pending"])).collect();
+
+    rpc.batch_request("eth_getTransactionCount", calls)
+        .await?
+        .into_iter()
+        .map(|res| match (res.result, res.error) {
+            (_, Some(err)) => Err(format!("RPC error {}: {}", err.code, err.message).into()),
+            (Some(result), None) => Ok(serde_json::from_value::<U64>(result)?.to::<u64>()),
+            (None, None) => Err("eth_getTransactionCount response had neither a result nor an error".into()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::eips::eip2930::AccessListItem;
+    use alloy::eips::eip7702::Authorization;
+    use alloy_consensus::TxEip7702;
+
+    use super::*;
+
+    /// Sign `tx` with both backends, as sign_and_encode_tx would, returning each encoding.
+    fn encode_with_both_backends(signer: &PrivateKeySigner, tx: &TypedTransaction) -> (Vec<u8>, Vec<u8>) {
+        let mut k256_tx = tx.clone();
+        let k256_signature = signer.sign_transaction_sync(&mut k256_tx).unwrap();
+
+        let key = SecretKey::from_byte_array(&signer.to_bytes().0).unwrap();
+        let secp256k1_signature = sign_hash_secp256k1(&key, tx.signature_hash());
+
+        (encode_signed_tx(k256_tx, k256_signature), encode_signed_tx(tx.clone(), secp256k1_signature))
+    }
+
+    #[test]
+    fn signing_backends_encode_identically() {
+        let mut rng = rand::rng();
+        for _ in 0..16 {
+            let signer = PrivateKeySigner::random();
+            let authority = PrivateKeySigner::random();
+            let nonce = rng.random();
+            let to = Address::from(rng.random::<[u8; 20]>());
+            let value = U256::from(rng.random::<u64>());
+            let input = Bytes::from(rng.random::<[u8; 68]>().to_vec());
+            let access_list = AccessList(vec![AccessListItem {
+                address: to,
+                storage_keys: vec![B256::from(rng.random::<[u8; 32]>())],
+            }]);
+            let authorization = Authorization { chain_id: U256::from(1337), address: to, nonce: rng.random() };
+            let authorization_signature = authority.sign_hash_sync(&authorization.signature_hash()).unwrap();
+
+            let txs = [
+                TypedTransaction::Legacy(TxLegacy {
+                    chain_id: Some(1337),
+                    nonce,
+                    gas_price: 100_000_000_000,
+                    gas_limit: 100_000,
+                    to: TxKind::Call(to),
+                    value,
+                    input: input.clone(),
+                }),
+                TypedTransaction::Eip2930(TxEip2930 {
+                    chain_id: 1337,
+                    nonce,
+                    gas_price: 100_000_000_000,
+                    gas_limit: 100_000,
+                    to: TxKind::Call(to),
+                    value,
+                    access_list: access_list.clone(),
+                    input: input.clone(),
+                }),
+                TypedTransaction::Eip1559(TxEip1559 {
+                    chain_id: 1337,
+                    nonce,
+                    gas_limit: 100_000,
+                    max_fee_per_gas: 100_000_000_000,
+                    max_priority_fee_per_gas: 1_000_000_000,
+                    to: TxKind::Create,
+                    value,
+                    access_list: access_list.clone(),
+                    input: input.clone(),
+                }),
+                TypedTransaction::Eip7702(TxEip7702 {
+                    chain_id: 1337,
+                    nonce,
+                    gas_limit: 100_000,
+                    max_fee_per_gas: 100_000_000_000,
+                    max_priority_fee_per_gas: 1_000_000_000,
+                    to,
+                    value,
+                    access_list,
+                    authorization_list: vec![authorization.into_signed(authorization_signature)],
+                    input,
+                }),
+            ];
+
+            for tx in &txs {
+                let (k256, secp256k1) = encode_with_both_backends(&signer, tx);
+                assert_eq!(k256, secp256k1, "encodings differ for {tx:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::Range;
+use std::time::Instant;
+
+use alloy::eips::eip4844::builder::{SidecarBuilder, SimpleCoder};
+use alloy::eips::eip4844::{BlobTransactionSidecar, FIELD_ELEMENTS_PER_BLOB};
+use alloy::eips::eip7594::BlobTransactionSidecarVariant;
+use alloy::primitives::{hex, Address, U256};
+use alloy_consensus::{TxEip4844, TxEip4844Variant, TxEip4844WithSidecar, TypedTransaction};
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+use thousands::Separable;
+
+use super::{random_recipient, TxGenerator, RECIPIENTS, SENDERS};
+use crate::config;
+
+/// Bytes of data that fit in a blob with the simple coder, which leaves the first byte of every
+/// field element empty. The first field element of each payload holds its length instead.
+const SIMPLE_CODER_BYTES_PER_BLOB: usize = FIELD_ELEMENTS_PER_BLOB as usize * 31;
+
+pub const DEFAULT_NUM_PAYLOADS: usize = 64;
+pub const DEFAULT_MAX_FEE_PER_BLOB_GAS: u64 = 1_000_000_000; // 1 gwei
+
+/// Blob txs sending no value to one of the first 1/recipient_distribution_factor accounts. Computing
+/// KZG commitments and proofs is far slower than signing, so a pool of sidecars is built upfront and
+/// each tx carries one picked at random.
+///
+/// Nodes refuse blob txs from accounts with non-blob txs pending (and vice versa), so when mixed with
+/// other workloads, they're sent from accounts reserved for them, picked uniformly.
+pub struct BlobTx {
+    gas_limit: u64,
+    senders: Option<Range<u32>>,
+    max_fee_per_blob_gas: u128,
+    sidecars: Vec<BlobTransactionSidecar>,
+}
+
+impl BlobTx {
+    pub fn new(
+        gas_limit: u64,
+        senders: Option<Range<u32>>,
+        blobs_per_tx: u32,
+        data: Option<&str>,
+        num_payloads: usize,
+        max_fee_per_blob_gas: u64,
+    ) -> Self {
+        if senders.as_ref().is_some_and(|senders| senders.is_empty()) {
+            panic!("[!] blob accounts must be non-zero.");
+        }
+
+        let payloads: Vec<Vec<u8>> = match data {
+            Some(data) => {
+                let data = hex::decode(data).unwrap_or_else(|e| panic!("[!] Invalid blob data: {e}"));
+                if data.is_empty() {
+                    panic!("[!] blob data must be non-empty.");
+                }
+                vec![data]
+            }
+            None => {
+                if blobs_per_tx == 0 || num_payloads == 0 {
+                    panic!("[!] blob blobs_per_tx and num_payloads must be non-zero.");
+                }
+                // Fill every blob but the length prefix with random data.
+                let payload_len = blobs_per_tx as usize * SIMPLE_CODER_BYTES_PER_BLOB - 31;
+                let mut rng = rand::rng();
+                (0..num_payloads)
+                    .map(|_| {
+                        let mut payload = vec![0u8; payload_len];
+                        rng.fill_bytes(&mut payload);
+                        payload
+                    })
+                    .collect()
+            }
+        };
+
+        let start = Instant::now();
+        println!("[~] Computing KZG commitments and proofs for {} blob payloads...", payloads.len());
+        let sidecars: Vec<BlobTransactionSidecar> = payloads
+            .par_iter()
+            .map(|payload| {
+                SidecarBuilder::<SimpleCoder>::from_slice(payload)
+                    .build()
+                    .unwrap_or_else(|e| panic!("[!] Failed to build blob sidecar: {e}"))
+            })
+            .collect();
+        println!(
+            "[+] Built {} blob sidecars of {} blobs each in {:.1?}",
+            sidecars.len().separate_with_commas(),
+            sidecars[0].blobs.len(),
+            start.elapsed()
+        );
+
+        Self { gas_limit, senders, max_fee_per_blob_gas: max_fee_per_blob_gas as u128, sidecars }
+    }
+}
+
+impl TxGenerator for BlobTx {
+    fn sample_sender(&self, rng: &mut dyn RngCore) -> u32 {
+        match &self.senders {
+            Some(senders) => rng.random_range(senders.clone()),
+            None => SENDERS.sample(rng),
+        }
+    }
+
+    fn build_tx(&self, rng: &mut dyn RngCore, _sender: Address, nonce: u64) -> TypedTransaction {
+        let config = &config::get().tx_gen_worker;
+        let sidecar = self.sidecars[rng.random_range(0..self.sidecars.len())].clone();
+
+        let tx = TxEip4844 {
+            chain_id: config.chain_id,
+            nonce,
+            gas_limit: self.gas_limit,
+            max_fee_per_gas: config.max_fee_per_gas as u128,
+            max_priority_fee_per_gas: config.max_priority_fee_per_gas as u128,
+            to: random_recipient(rng, &RECIPIENTS),
+            value: U256::ZERO,
+            access_list: Default::default(),
+            blob_versioned_hashes: sidecar.versioned_hashes().collect(),
+            max_fee_per_blob_gas: self.max_fee_per_blob_gas,
+            input: Default::default(),
+        };
+        // Signed and encoded with the sidecar, i.e. in the network form nodes expect over RPC.
+        TypedTransaction::Eip4844(TxEip4844Variant::TxEip4844WithSidecar(TxEip4844WithSidecar::from_tx_and_sidecar(
+            tx,
+            BlobTransactionSidecarVariant::Eip4844(sidecar),
+        )))
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use alloy::primitives::{hex, Address, Bytes, TxKind, B256, U256};
+use alloy::sol_types::SolCall;
+use rand::RngCore;
+use serde_json::Value;
+use thousands::Separable;
+
+use super::{build_typed_tx, fetch_pending_nonces, next_nonce, sign_and_encode_tx, NONCE_MAP, SIGNER_LIST};
+use crate::config::{self, WorkloadKind};
+use crate::contracts::{erc20_init_code, pair_init_code, ERC20};
+use crate::rpc::RpcClient;
+
+/// Gas budgeted per mint tx, plus per account minted to (a cold storage write and its calldata).
+const MINT_BASE_GAS: u64 = 50_000;
+const MINT_GAS_PER_ACCOUNT: u64 = 30_000;
+
+/// Mint txs sent before waiting for them to be included, as nodes cap how many txs per sender they'll pool.
+const MAX_PENDING_MINTS: usize = 16;
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Token deployed by the bootstrap, if it ran.
+static DEPLOYED_TOKEN: OnceLock<Address> = OnceLock::new();
+
+/// AMM pair and its second token (the first being DEPLOYED_TOKEN) deployed by the bootstrap, if it
+/// ran with a swap workload configured.
+static DEPLOYED_AMM: OnceLock<(Address, Address)> = OnceLock::new();
+
+/// Token erc20_transfer txs are sent to: the one deployed by the bootstrap if it ran, else token_contract_address.
+pub fn token_address() -> Address {
+    if let Some(token) = DEPLOYED_TOKEN.get() {
+        return *token;
+    }
+    let token = &config::get().tx_gen_worker.token_contract_address;
+    token.parse().unwrap_or_else(|e| panic!("[!] Invalid token_contract_address {token}: {e}"))
+}
+
+/// AMM pair and second token deployed by the bootstrap, if any.
+pub fn deployed_amm() -> Option<(Address, Address)> {
+    DEPLOYED_AMM.get().copied()
+}
+
+/// Deploy an ERC20 from account 0 and mint it to every account, waiting for each tx to be included. If a
+/// swap workload is configured, also deploy a second one and an AMM pair between them, minting it liquidity.
+pub async fn run_bootstrap() {
+    let start = Instant::now();
+    let config = &config::get().bootstrap;
+    let rpc = RpcClient::new(config::get().network_worker.rpc_url());
+    let mut rng = rand::rng();
+
+    // Account 0 sends every bootstrap tx, so pick up from its pending nonce.
+    let nonces = fetch_pending_nonces(&rpc, &[0])
+        .await
+        .unwrap_or_else(|e| panic!("[!] Failed to fetch the nonce of account 0 from the target node: {e}"));
+    NONCE_MAP.insert(0, nonces[0]);
+
+    let init_code = match &config.token_init_code {
+        Some(init_code) => {
+            hex::decode(init_code).unwrap_or_else(|e| panic!("[!] Invalid bootstrap.token_init_code: {e}")).into()
+        }
+        None => erc20_init_code(),
+    };
+    let token = deploy(&rpc, &mut rng, "ERC20", init_code.clone()).await;
+    mint_to_all(&rpc, &mut rng, token).await;
+    println!(
+        "[+] Bootstrapped ERC20 at {token}, minting {} to each of {} accounts in {:.1?}",
+        config.mint_amount.separate_with_commas(),
+        SIGNER_LIST.len().separate_with_commas(),
+        start.elapsed()
+    );
+    DEPLOYED_TOKEN.set(token).unwrap();
+
+    if config::get().workloads.iter().any(|workload| matches!(workload.kind, WorkloadKind::Swap { .. })) {
+        let start = Instant::now();
+        let token1 = deploy(&rpc, &mut rng, "second ERC20", init_code).await;
+        mint_to_all(&rpc, &mut rng, token1).await;
+
+        let liquidity = config.amm_liquidity;
+        let pair = deploy(&rpc, &mut rng, "AMM pair", pair_init_code(token, token1, liquidity, liquidity)).await;
+        let mut hashes = Vec::with_capacity(2);
+        for token in [token, token1] {
+            let input = ERC20::mintCall { to: vec![pair], amount: liquidity }.abi_encode();
+            let tx =
+                build_typed_tx(&mut rng, next_nonce(0), MINT_BASE_GAS, TxKind::Call(token), U256::ZERO, input.into());
+            hashes.push(send_tx(&rpc, sign_and_encode_tx(0, tx)).await);
+        }
+        wait_for_receipts(&rpc, hashes).await;
+
+        println!(
+            "[+] Bootstrapped AMM pair at {pair} between {token} and {token1}, with {} of each in {:.1?}",
+            config.amm_liquidity.separate_with_commas(),
+            start.elapsed()
+        );
+        DEPLOYED_AMM.set((pair, token1)).unwrap();
+    }
+}
+
+/// Deploy a contract from account 0, returning its address once it's included.
+async fn deploy(rpc: &RpcClient, rng: &mut dyn RngCore, name: &str, init_code: Bytes) -> Address {
+    let deployer = SIGNER_LIST[0].address();
+    let nonce = next_nonce(0);
+    let address = deployer.create(nonce);
+
+    println!("[~] Bootstrapping: deploying {name} from {deployer}...");
+    let tx =
+        build_typed_tx(rng, nonce, config::get().bootstrap.deploy_gas_limit, TxKind::Create, U256::ZERO, init_code);
+    let hash = send_tx(rpc, sign_and_encode_tx(0, tx)).await;
+    wait_for_receipts(rpc, vec![hash]).await;
+    println!("[+] Deployed {name} at {address}");
+    address
+}
+
+/// Mint mint_amount of a token deployed from account 0 to every account.
+async fn mint_to_all(rpc: &RpcClient, rng: &mut dyn RngCore, token: Address) {
+    let config = &config::get().bootstrap;
+
+    let recipients: Vec<Address> = SIGNER_LIST.iter().map(|signer| signer.address()).collect();
+    let batches: Vec<&[Address]> = recipients.chunks(config.mint_batch_size).collect();
+    let mut minted = 0;
+    for pending_batches in batches.chunks(MAX_PENDING_MINTS) {
+        let mut hashes = Vec::with_capacity(pending_batches.len());
+        for batch in pending_batches {
+            let input = ERC20::mintCall { to: batch.to_vec(), amount: U256::from(config.mint_amount) }.abi_encode();
+            let gas_limit = MINT_BASE_GAS + MINT_GAS_PER_ACCOUNT * batch.len() as u64;
+            let tx = build_typed_tx(rng, next_nonce(0), gas_limit, TxKind::Call(token), U256::ZERO, input.into());
+            hashes.push(send_tx(rpc, sign_and_encode_tx(0, tx)).await);
+            minted += batch.len();
+        }
+        wait_for_receipts(rpc, hashes).await;
+        println!(
+            "[*] Minted to {} / {} accounts",
+            minted.separate_with_commas(),
+            recipients.len().separate_with_commas()
+        );
+    }
+}
+
+async fn send_tx(rpc: &RpcClient, raw: Vec<u8>) -> B256 {
+    rpc.request("eth_sendRawTransaction", serde_json::json!([format!("0x{}", hex::encode(raw))]))
+        .await
+        .unwrap_or_else(|e| panic!("[!] Failed to send bootstrap tx: {e}"))
+}
+
+/// Poll for the receipts of txs until all of them are included, panicking if any reverted or took too long.
+async fn wait_for_receipts(rpc: &RpcClient, mut hashes: Vec<B256>) {
+    let deadline = Instant::now() + Duration::from_secs(config::get().bootstrap.receipt_timeout_secs);
+    loop {
+        let calls = hashes.iter().map(|hash| serde_json::json!([hash])).collect();
+        let responses = rpc
+            .batch_request("eth_getTransactionReceipt", calls)
+            .await
+            .unwrap_or_else(|e| panic!("[!] Failed to fetch bootstrap tx receipts: {e}"));
+
+        let mut pending = Vec::new();
+        for (hash, res) in hashes.into_iter().zip(responses) {
+            match res.result {
+                Some(Value::Null) | None => pending.push(hash),
+                Some(receipt) if receipt["status"] == "0x1" => {}
+                Some(_) => panic!("[!] Bootstrap tx {hash} reverted."),
+            }
+        }
+        if pending.is_empty() {
+            return;
+        }
+        if Instant::now() > deadline {
+            panic!("[!] Bootstrap tx {} wasn't included in time.", pending[0]);
+        }
+
+        hashes = pending;
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,78 @@
+use alloy::primitives::{hex, Address, Bytes, TxKind, U256};
+use alloy_consensus::TypedTransaction;
+use rand::RngCore;
+
+use super::{build_typed_tx, TxGenerator};
+
+/// Runtime code that sets every 32 byte word of its calldata as a storage slot (to 1):
+///
+///   PUSH1 0                         offset = 0
+///   loop: JUMPDEST
+///   CALLDATASIZE DUP2 LT ISZERO     if !(offset < calldatasize)
+///   PUSH1 end JUMPI                   goto end
+///   PUSH1 1 DUP2 CALLDATALOAD SSTORE  sstore(calldataload(offset), 1)
+///   PUSH1 32 ADD                    offset += 32
+///   PUSH1 loop JUMP
+///   end: JUMPDEST STOP
+const STORAGE_WRITER_RUNTIME: &str = "60005b3681101560155760018135556020016002565b00";
+
+/// Init code that copies the runtime code appended to it into memory and returns it:
+/// PUSH1 len DUP1 PUSH1 11 PUSH1 0 CODECOPY PUSH1 0 RETURN.
+const DEPLOYER_PREFIX: &str = "601780600b6000396000f3";
+
+/// Contract creations running the given init code, or deploying a storage writer by default.
+pub struct ContractDeploy {
+    init_code: Bytes,
+    gas_limit: u64,
+}
+
+impl ContractDeploy {
+    pub fn new(gas_limit: u64, init_code: Option<&str>) -> Self {
+        let init_code = match init_code {
+            Some(init_code) => parse_hex("init_code", init_code),
+            None => parse_hex("init_code", &format!("{DEPLOYER_PREFIX}{STORAGE_WRITER_RUNTIME}")),
+        };
+        Self { init_code, gas_limit }
+    }
+}
+
+impl TxGenerator for ContractDeploy {
+    fn build_tx(&self, rng: &mut dyn RngCore, _sender: Address, nonce: u64) -> TypedTransaction {
+        build_typed_tx(rng, nonce, self.gas_limit, TxKind::Create, U256::ZERO, self.init_code.clone())
+    }
+}
+
+/// Calls to a contract with fixed calldata followed by random words, e.g. fresh
+/// slots for a storage writer to write, so each call does storage-heavy work.
+pub struct ContractCall {
+    to: Address,
+    input: Vec<u8>,
+    random_words: u32,
+    gas_limit: u64,
+}
+
+impl ContractCall {
+    pub fn new(gas_limit: u64, to: &str, input: Option<&str>, random_words: u32) -> Self {
+        Self {
+            to: to.parse().unwrap_or_else(|e| panic!("[!] Invalid contract_call address {to}: {e}")),
+            input: input.map_or(Vec::new(), |input| parse_hex("input", input).into()),
+            random_words,
+            gas_limit,
+        }
+    }
+}
+
+impl TxGenerator for ContractCall {
+    fn build_tx(&self, rng: &mut dyn RngCore, _sender: Address, nonce: u64) -> TypedTransaction {
+        let mut input = Vec::with_capacity(self.input.len() + self.random_words as usize * 32);
+        input.extend_from_slice(&self.input);
+        input.resize(self.input.len() + self.random_words as usize * 32, 0);
+        rng.fill_bytes(&mut input[self.input.len()..]);
+
+        build_typed_tx(rng, nonce, self.gas_limit, TxKind::Call(self.to), U256::ZERO, input.into())
+    }
+}
+
+fn parse_hex(field: &str, value: &str) -> Bytes {
+    hex::decode(value).unwrap_or_else(|e| panic!("[!] Invalid workload {field} {value}: {e}")).into()
+}
@@ -0,0 +1,188 @@
+use std::cell::Cell;
+
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::{Rng, RngCore};
+
+use crate::config::AccountDistribution;
+
+thread_local! {
+    // The TxGen worker this thread runs and the number of them, which partitioned distributions split
+    // accounts between. Threads other than TxGen workers (e.g. generating a corpus) get all of them.
+    static WORKER_PARTITION: Cell<(u32, u32)> = const { Cell::new((0, 1)) };
+}
+
+/// Set the partition of accounts this thread picks from with partitioned distributions.
+pub fn set_worker_partition(worker_id: u32, num_workers: u32) {
+    WORKER_PARTITION.set((worker_id, num_workers));
+}
+
+/// Picks indices of the first `num_accounts` accounts by a configured distribution.
+pub struct AccountSampler {
+    num_accounts: u32,
+    kind: SamplerKind,
+}
+
+enum SamplerKind {
+    Uniform,
+    Zipf(WeightedIndex<f64>),
+    Hotspot { hot_accounts: u32, hot_fraction: f64 },
+    Partitioned { num_senders: u32 },
+}
+
+impl AccountSampler {
+    /// Panics (naming the distribution `name`) if it's misconfigured for `num_accounts` accounts. Partitioned
+    /// distributions split the first `num_senders` accounts between workers, then pick from the first
+    /// num_accounts/num_senders of the worker's slice, so recipients picked among fewer accounts stay inside it.
+    pub fn new(name: &str, distribution: &AccountDistribution, num_accounts: u32, num_senders: u32) -> Self {
+        if num_accounts == 0 {
+            panic!("[!] {name} has no accounts to pick from.");
+        }
+
+        let kind = match *distribution {
+            AccountDistribution::Uniform => SamplerKind::Uniform,
+            AccountDistribution::Zipf { exponent } => {
+                if !(exponent >= 0.0 && exponent.is_finite()) {
+                    panic!("[!] {name} zipf exponent must be non-negative.");
+                }
+                // Precomputed so each pick is a binary search, rather than rejection sampling per pick.
+                SamplerKind::Zipf(
+                    WeightedIndex::new((1..=num_accounts).map(|rank| (rank as f64).powf(-exponent)))
+                        .unwrap_or_else(|e| panic!("[!] Invalid {name} zipf weights: {e}")),
+                )
+            }
+            AccountDistribution::Hotspot { hot_accounts, hot_fraction } => {
+                if hot_accounts == 0 || hot_accounts > num_accounts {
+                    panic!(
+                        "[!] {name} hot_accounts must be non-zero and at most the {num_accounts} accounts picked from."
+                    );
+                }
+                if !(0.0..=1.0).contains(&hot_fraction) {
+                    panic!("[!] {name} hot_fraction must be between 0 and 1.");
+                }
+                SamplerKind::Hotspot { hot_accounts, hot_fraction }
+            }
+            AccountDistribution::Partitioned => SamplerKind::Partitioned { num_senders },
+        };
+
+        Self { num_accounts, kind }
+    }
+
+    pub fn sample(&self, rng: &mut dyn RngCore) -> u32 {
+        match &self.kind {
+            SamplerKind::Uniform => rng.random_range(0..self.num_accounts),
+            SamplerKind::Zipf(weights) => weights.sample(rng) as u32,
+            &SamplerKind::Hotspot { hot_accounts, hot_fraction } => {
+                if hot_accounts == self.num_accounts || rng.random_bool(hot_fraction) {
+                    rng.random_range(0..hot_accounts)
+                } else {
+                    rng.random_range(hot_accounts..self.num_accounts)
+                }
+            }
+            &SamplerKind::Partitioned { num_senders } => {
+                let (worker_id, num_workers) = WORKER_PARTITION.get();
+                let start = (num_senders as u64 * worker_id as u64 / num_workers as u64) as u32;
+                let end = (num_senders as u64 * (worker_id as u64 + 1) / num_workers as u64) as u32;
+                let len =
+                    ((end - start) as u64 * self.num_accounts.min(num_senders) as u64 / num_senders as u64) as u32;
+                // With fewer accounts than workers, some workers share one.
+                rng.random_range(start..start + len.max(1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const SAMPLES: usize = 100_000;
+
+    fn samples(sampler: &AccountSampler) -> Vec<u32> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..SAMPLES).map(|_| sampler.sample(&mut rng)).collect()
+    }
+
+    #[test]
+    fn partitioned_picks_stay_inside_the_worker_slice() {
+        // 1,000 senders, 1/20 of accounts receiving, split between 4 workers.
+        let senders = AccountSampler::new("senders", &AccountDistribution::Partitioned, 1_000, 1_000);
+        let recipients = AccountSampler::new("recipients", &AccountDistribution::Partitioned, 50, 1_000);
+        for worker_id in 0..4 {
+            set_worker_partition(worker_id, 4);
+            let start = worker_id * 250;
+
+            let picked = samples(&senders);
+            assert!(picked.iter().all(|index| (start..start + 250).contains(index)));
+            assert_eq!(*picked.iter().min().unwrap(), start);
+            assert_eq!(*picked.iter().max().unwrap(), start + 249);
+
+            // Recipients come from the first 1/20 of the worker's own senders.
+            let picked = samples(&recipients);
+            assert!(picked.iter().all(|index| (start..start + 12).contains(index)));
+        }
+
+        // More workers than accounts still picks one.
+        set_worker_partition(7, 8);
+        assert!(samples(&AccountSampler::new("recipients", &AccountDistribution::Partitioned, 2, 4))
+            .iter()
+            .all(|index| *index == 3));
+    }
+
+    #[test]
+    fn hotspot_picks_hot_accounts_hot_fraction_of_the_time() {
+        let hotspot = |hot_fraction| {
+            let sampler = AccountSampler::new(
+                "senders",
+                &AccountDistribution::Hotspot { hot_accounts: 10, hot_fraction },
+                1_000,
+                1_000,
+            );
+            let picked = samples(&sampler);
+            assert!(picked.iter().all(|index| *index < 1_000));
+            picked.iter().filter(|index| **index < 10).count() as f64 / SAMPLES as f64
+        };
+
+        assert!((hotspot(0.9) - 0.9).abs() < 0.01);
+        assert!((hotspot(0.25) - 0.25).abs() < 0.01);
+        assert_eq!(hotspot(0.0), 0.0);
+        assert_eq!(hotspot(1.0), 1.0);
+    }
+
+    #[test]
+    fn zipf_picks_by_rank() {
+        let sampler = AccountSampler::new("senders", &AccountDistribution::Zipf { exponent: 1.0 }, 100, 100);
+        let mut counts = [0usize; 100];
+        for index in samples(&sampler) {
+            counts[index as usize] += 1;
+        }
+
+        // Probability proportional to 1/(k+1): the top account is picked twice as often as the next, 10x the 10th.
+        let ratio = |a: usize, b: usize| counts[a] as f64 / counts[b] as f64;
+        assert!((ratio(0, 1) - 2.0).abs() < 0.1);
+        assert!((ratio(0, 9) - 10.0).abs() < 1.0);
+        assert!(counts.iter().all(|count| *count > 0));
+
+        // An exponent of 0 is uniform.
+        let sampler = AccountSampler::new("senders", &AccountDistribution::Zipf { exponent: 0.0 }, 4, 4);
+        let mut counts = [0usize; 4];
+        for index in samples(&sampler) {
+            counts[index as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| (*count as f64 / SAMPLES as f64 - 0.25).abs() < 0.01));
+    }
+
+    #[test]
+    #[should_panic(expected = "hot_fraction must be between 0 and 1")]
+    fn hotspot_rejects_fraction_over_one() {
+        AccountSampler::new(
+            "senders",
+            &AccountDistribution::Hotspot { hot_accounts: 10, hot_fraction: 90.0 },
+            1_000,
+            1_000,
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use alloy::eips::eip2930::{AccessList, AccessListItem};
+use alloy::primitives::{Address, TxKind, U256};
+use alloy::sol_types::SolCall;
+use alloy_consensus::TypedTransaction;
+use rand::{Rng, RngCore};
+
+use super::{bootstrap, build_typed_tx_with_access_list, random_recipient, TxGenerator, RECIPIENTS};
+use crate::config;
+use crate::contracts::{erc20_balance_slot, ERC20};
+
+/// Transfers of the token (deployed by the bootstrap, or configured) to
+/// one of the first 1/recipient_distribution_factor accounts.
+pub struct Erc20Transfer {
+    token: Address,
+    gas_limit: u64,
+}
+
+impl Erc20Transfer {
+    pub fn new(gas_limit: u64) -> Self {
+        Self { token: bootstrap::token_address(), gas_limit }
+    }
+}
+
+impl TxGenerator for Erc20Transfer {
+    fn build_tx(&self, rng: &mut dyn RngCore, sender: Address, nonce: u64) -> TypedTransaction {
+        let config = &config::get().tx_gen_worker;
+
+        let recipient = random_recipient(rng, &RECIPIENTS); // Send to 1/Nth of the accounts.
+        let input =
+            ERC20::transferCall { to: recipient, amount: U256::from(rng.random_range(1..=config.max_transfer_amount)) }
+                .abi_encode()
+                .into();
+
+        // Transfers only touch the sender's and recipient's balances, so they're known upfront.
+        let access_list = if config.access_lists {
+            AccessList(vec![AccessListItem {
+                address: self.token,
+                storage_keys: vec![erc20_balance_slot(sender), erc20_balance_slot(recipient)],
+            }])
+        } else {
+            AccessList::default()
+        };
+
+        build_typed_tx_with_access_list(
+            rng,
+            nonce,
+            self.gas_limit,
+            TxKind::Call(self.token),
+            U256::ZERO,
+            input,
+            access_list,
+        )
+    }
+}
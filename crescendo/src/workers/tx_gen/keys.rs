@@ -0,0 +1,104 @@
+use std::ops::Range;
+use std::path::Path;
+use std::{fs, io};
+
+use alloy::primitives::{keccak256, B256};
+use alloy_signer_local::coins_bip39::English;
+use alloy_signer_local::{MnemonicBuilder, PrivateKeySigner};
+use rayon::prelude::*;
+use thousands::Separable;
+
+use crate::config::TxGenWorkerConfig;
+
+/// Signers of the config's accounts, read from private_keys_path if set, else derived from the mnemonic.
+pub fn load_signers(config: &TxGenWorkerConfig) -> Vec<PrivateKeySigner> {
+    if let Some(path) = &config.private_keys_path {
+        return read_private_keys(path, config.num_accounts as usize);
+    }
+
+    let Some(cache_path) = &config.signer_cache_path else {
+        return derive_signers(&config.mnemonic, 0..config.num_accounts);
+    };
+
+    // Only derive the keys the cache is missing, then write them back for next time.
+    let mnemonic_hash = keccak256(&config.mnemonic);
+    let mut signers = read_signer_cache(cache_path, mnemonic_hash, config.num_accounts as usize);
+    let cached = signers.len() as u32;
+    println!(
+        "[*] Read {} of {} keys from the signer cache",
+        cached.separate_with_commas(),
+        config.num_accounts.separate_with_commas()
+    );
+    if cached < config.num_accounts {
+        signers.extend(derive_signers(&config.mnemonic, cached..config.num_accounts));
+        write_signer_cache(cache_path, mnemonic_hash, &signers);
+    }
+    signers
+}
+
+fn derive_signers(mnemonic: &str, indices: Range<u32>) -> Vec<PrivateKeySigner> {
+    indices
+        .into_par_iter()
+        .map(|i| MnemonicBuilder::<English>::default().phrase(mnemonic).index(i).unwrap().build().unwrap())
+        .collect()
+}
+
+/// Read the first `num_accounts` keys from a file of hex keys, one per line or as a JSON array.
+fn read_private_keys(path: &Path, num_accounts: usize) -> Vec<PrivateKeySigner> {
+    let contents = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("[!] Failed to read private_keys_path {}: {e}", path.display()));
+    let keys: Vec<String> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(&contents).unwrap_or_else(|e| panic!("[!] Invalid JSON in private_keys_path: {e}"))
+    } else {
+        contents.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect()
+    };
+
+    if keys.len() < num_accounts {
+        panic!(
+            "[!] private_keys_path only has {} keys, fewer than num_accounts ({}).",
+            keys.len().separate_with_commas(),
+            num_accounts.separate_with_commas()
+        );
+    }
+
+    keys[..num_accounts]
+        .par_iter()
+        .enumerate()
+        .map(|(i, key)| key.parse().unwrap_or_else(|e| panic!("[!] Invalid key #{i} in private_keys_path: {e}")))
+        .collect()
+}
+
+/// Read up to `max_keys` keys from a signer cache: the keccak256 of the mnemonic they were derived from,
+/// followed by each raw 32 byte key in order. Returns no keys if it's missing or from another mnemonic.
+fn read_signer_cache(path: &Path, mnemonic_hash: B256, max_keys: usize) -> Vec<PrivateKeySigner> {
+    let cache = match fs::read(path) {
+        Ok(cache) => cache,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => panic!("[!] Failed to read signer cache {}: {e}", path.display()),
+    };
+
+    let Some(keys) = cache.strip_prefix(mnemonic_hash.as_slice()) else {
+        println!("[!] Signer cache {} is from another mnemonic, re-deriving keys", path.display());
+        return Vec::new();
+    };
+
+    let signers: Result<Vec<PrivateKeySigner>, _> =
+        keys.par_chunks_exact(32).take(max_keys).map(PrivateKeySigner::from_slice).collect();
+    signers.unwrap_or_else(|e| {
+        println!("[!] Signer cache {} is corrupt ({e}), re-deriving keys", path.display());
+        Vec::new()
+    })
+}
+
+fn write_signer_cache(path: &Path, mnemonic_hash: B256, signers: &[PrivateKeySigner]) {
+    let mut cache = Vec::with_capacity(32 * (signers.len() + 1));
+    cache.extend_from_slice(mnemonic_hash.as_slice());
+    for signer in signers {
+        cache.extend_from_slice(signer.to_bytes().as_slice());
+    }
+
+    match fs::write(path, cache) {
+        Ok(()) => println!("[+] Cached {} keys to {}", signers.len().separate_with_commas(), path.display()),
+        Err(e) => println!("[!] Failed to write signer cache {}: {e}", path.display()),
+    }
+}
@@ -0,0 +1,34 @@
+use alloy::primitives::{Address, TxKind, U256};
+use alloy_consensus::TypedTransaction;
+use rand::{Rng, RngCore};
+
+use super::distribution::AccountSampler;
+use super::{build_typed_tx, new_recipient_sampler, random_recipient, TxGenerator};
+
+/// Intrinsic gas of a tx without calldata, all a transfer to an account without code uses.
+pub const NATIVE_TRANSFER_GAS: u64 = 21_000;
+
+/// Plain value transfers to one of the first 1/recipient_distribution_factor accounts.
+pub struct NativeTransfer {
+    gas_limit: u64,
+    min_value: u64,
+    max_value: u64,
+    recipients: AccountSampler,
+}
+
+impl NativeTransfer {
+    pub fn new(gas_limit: u64, min_value: u64, max_value: u64, recipient_distribution_factor: u32) -> Self {
+        if min_value > max_value {
+            panic!("[!] native_transfer min_value must be at most max_value.");
+        }
+        Self { gas_limit, min_value, max_value, recipients: new_recipient_sampler(recipient_distribution_factor) }
+    }
+}
+
+impl TxGenerator for NativeTransfer {
+    fn build_tx(&self, rng: &mut dyn RngCore, _sender: Address, nonce: u64) -> TypedTransaction {
+        let recipient = random_recipient(rng, &self.recipients);
+        let value = U256::from(rng.random_range(self.min_value..=self.max_value));
+        build_typed_tx(rng, nonce, self.gas_limit, TxKind::Call(recipient), value, Default::default())
+    }
+}
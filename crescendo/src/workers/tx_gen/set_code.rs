@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+use alloy::eips::eip7702::constants::PER_EMPTY_ACCOUNT_COST;
+use alloy::eips::eip7702::{Authorization, SignedAuthorization};
+use alloy::primitives::{hex, Address, Bytes, U256};
+use alloy_consensus::{TxEip7702, TypedTransaction};
+use rand::{Rng, RngCore};
+
+use super::{next_nonce, random_recipient, sign_hash, TxGenerator, RECIPIENTS, SIGNER_LIST};
+use crate::config;
+
+pub const DEFAULT_NUM_AUTHORITIES: u32 = 1_000;
+
+/// EIP-7702 txs carrying authorizations delegating random authorities to `delegate`. Authorities are
+/// accounts reserved for them that never send txs, so an authorization bumping one's nonce can't leave
+/// a gap in any sender's. Their nonces are still taken from the nonce map, so an authorization applied
+/// out of order (e.g. included before an earlier one of the same authority) is skipped, and so are the
+/// authority's later ones until its nonce is synced again on the next run. Their txs go through either way.
+pub struct SetCode {
+    gas_limit: u64,
+    delegate: Address,
+    authorities: Range<u32>,
+    authorizations_per_tx: u32,
+    call_input: Option<Bytes>,
+}
+
+impl SetCode {
+    pub fn new(
+        gas_limit: Option<u64>,
+        delegate: &str,
+        authorities: Range<u32>,
+        authorizations_per_tx: u32,
+        call_input: Option<&str>,
+    ) -> Self {
+        if authorities.is_empty() || authorizations_per_tx == 0 {
+            panic!("[!] set_code authorities and authorizations_per_tx must be non-zero.");
+        }
+        Self {
+            // Authorizations cost 25_000 each upfront (refunded down to 12_500 if the authority already exists).
+            gas_limit: gas_limit.unwrap_or(
+                config::get().tx_gen_worker.gas_limit + PER_EMPTY_ACCOUNT_COST * authorizations_per_tx as u64,
+            ),
+            delegate: delegate.parse().unwrap_or_else(|e| panic!("[!] Invalid set_code delegate {delegate}: {e}")),
+            authorities,
+            authorizations_per_tx,
+            call_input: call_input.map(|input| {
+                hex::decode(input).unwrap_or_else(|e| panic!("[!] Invalid set_code call_input: {e}")).into()
+            }),
+        }
+    }
+}
+
+impl TxGenerator for SetCode {
+    fn build_tx(&self, rng: &mut dyn RngCore, _sender: Address, nonce: u64) -> TypedTransaction {
+        let config = &config::get().tx_gen_worker;
+
+        let authorities: Vec<u32> =
+            (0..self.authorizations_per_tx).map(|_| rng.random_range(self.authorities.clone())).collect();
+        let authorization_list: Vec<SignedAuthorization> = authorities
+            .iter()
+            .map(|&authority| {
+                let authorization = Authorization {
+                    chain_id: U256::from(config.chain_id),
+                    address: self.delegate,
+                    nonce: next_nonce(authority),
+                };
+                let signature = sign_hash(authority, authorization.signature_hash());
+                authorization.into_signed(signature)
+            })
+            .collect();
+
+        // Authorizations are applied before the call, so calling an authority runs the delegate's code as it.
+        let (to, input) = match &self.call_input {
+            Some(input) => (SIGNER_LIST[authorities[0] as usize].address(), input.clone()),
+            None => (random_recipient(rng, &RECIPIENTS), Bytes::new()),
+        };
+
+        TypedTransaction::Eip7702(TxEip7702 {
+            chain_id: config.chain_id,
+            nonce,
+            gas_limit: self.gas_limit,
+            max_fee_per_gas: config.max_fee_per_gas as u128,
+            max_priority_fee_per_gas: config.max_priority_fee_per_gas as u128,
+            to,
+            value: U256::ZERO,
+            access_list: Default::default(),
+            authorization_list,
+            input,
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use alloy::primitives::{Address, Bytes, TxKind, U256};
+use alloy::sol_types::SolCall;
+use alloy_consensus::TypedTransaction;
+use dashmap::DashMap;
+use rand::{Rng, RngCore};
+use thousands::Separable;
+
+use super::{bootstrap, build_typed_tx, unreserved_accounts, TxGenerator, NONCE_MAP, SIGNER_LIST};
+use crate::config;
+use crate::contracts::{Pair, ERC20};
+use crate::rpc::{RpcClient, RpcResult};
+
+/// Allowance from which a token counts as already approved. Approvals are for U256::MAX,
+/// which swaps never bring anywhere near this.
+const APPROVED_ALLOWANCE: U256 = U256::from_limbs([0, 0, 0, 1 << 63]);
+
+/// Swaps of min_amount_in..=max_amount_in of a random side of an AMM pair (deployed by the bootstrap,
+/// or configured) for the other. Each account's first two txs approve the pair to spend its tokens
+/// (unless it already did in an earlier run against the same chain), so every later tx is a swap, all
+/// of which write the pair's reserves.
+pub struct Swap {
+    gas_limit: u64,
+    pair: Address,
+    tokens: [Address; 2],
+    min_amount_in: u64,
+    max_amount_in: u64,
+
+    approvals_sent: DashMap<Address, usize>,
+}
+
+impl Swap {
+    pub fn new(
+        gas_limit: u64,
+        pair: Option<&str>,
+        token1: Option<&str>,
+        min_amount_in: u64,
+        max_amount_in: u64,
+    ) -> Self {
+        if min_amount_in == 0 || min_amount_in > max_amount_in {
+            panic!("[!] swap min_amount_in must be non-zero and at most max_amount_in.");
+        }
+
+        let (pair, token1) = bootstrap::deployed_amm().unwrap_or_else(|| {
+            let parse = |name: &str, address: Option<&str>| -> Address {
+                let address = address.unwrap_or_else(|| panic!("[!] swap {name} must be set without the bootstrap."));
+                address.parse().unwrap_or_else(|e| panic!("[!] Invalid swap {name} {address}: {e}"))
+            };
+            (parse("pair", pair), parse("token1", token1))
+        });
+
+        Self {
+            gas_limit,
+            pair,
+            tokens: [bootstrap::token_address(), token1],
+            min_amount_in,
+            max_amount_in,
+            approvals_sent: DashMap::new(),
+        }
+    }
+
+    /// Skip approving for senders that already approved the pair to spend the tokens in an earlier run
+    /// against the same chain, querying their allowances. Only senders that have sent txs are checked.
+    async fn sync_approvals(&self, rpc: &RpcClient) -> RpcResult<()> {
+        let senders: Vec<Address> = (0..unreserved_accounts())
+            .filter(|account_index| NONCE_MAP.get(account_index).is_some_and(|nonce| *nonce > 0))
+            .map(|account_index| SIGNER_LIST[account_index as usize].address())
+            .collect();
+
+        let mut fully_approved = 0usize;
+        for batch in senders.chunks(config::get().tx_gen_worker.nonce_sync_batch_size) {
+            let mut approvals = vec![0; batch.len()];
+            for (token_index, &token) in self.tokens.iter().enumerate() {
+                let calls = batch
+                    .iter()
+                    .map(|&owner| {
+                        let input = Bytes::from(ERC20::allowanceCall { owner, spender: self.pair }.abi_encode());
+                        serde_json::json!([{ "to": token, "data": input }, "latest"])
+                    })
+                    .collect();
+                let responses = rpc.batch_request("eth_call", calls).await?;
+                for (approvals, res) in approvals.iter_mut().zip(responses) {
+                    let allowance = match (res.result, res.error) {
+                        (_, Some(err)) => return Err(format!("RPC error {}: {}", err.code, err.message).into()),
+                        (Some(result), None) => {
+                            let output = serde_json::from_value::<Bytes>(result)?;
+                            ERC20::allowanceCall::abi_decode_returns(&output)?
+                        }
+                        (None, None) => return Err("eth_call response had neither a result nor an error".into()),
+                    };
+                    // Tokens are approved in order, so a sender resumes from its first unapproved one.
+                    if *approvals == token_index && allowance >= APPROVED_ALLOWANCE {
+                        *approvals += 1;
+                    }
+                }
+            }
+
+            for (&sender, approvals) in batch.iter().zip(approvals) {
+                if approvals > 0 {
+                    self.approvals_sent.insert(sender, approvals);
+                }
+                fully_approved += (approvals == self.tokens.len()) as usize;
+            }
+        }
+
+        println!(
+            "[+] {} of {} senders that sent txs before already approved the swap pair {}",
+            fully_approved.separate_with_commas(),
+            senders.len().separate_with_commas(),
+            self.pair
+        );
+        Ok(())
+    }
+}
+
+impl TxGenerator for Swap {
+    fn build_tx(&self, rng: &mut dyn RngCore, sender: Address, nonce: u64) -> TypedTransaction {
+        let approvals_sent = {
+            let mut approvals_sent = self.approvals_sent.entry(sender).or_insert(0);
+            *approvals_sent += 1;
+            *approvals_sent - 1
+        };
+
+        if let Some(&token) = self.tokens.get(approvals_sent) {
+            let input = ERC20::approveCall { spender: self.pair, amount: U256::MAX }.abi_encode();
+            return build_typed_tx(rng, nonce, self.gas_limit, TxKind::Call(token), U256::ZERO, input.into());
+        }
+
+        let input = Pair::swapCall {
+            amountIn: U256::from(rng.random_range(self.min_amount_in..=self.max_amount_in)),
+            zeroForOne: rng.random_bool(0.5),
+        }
+        .abi_encode();
+        build_typed_tx(rng, nonce, self.gas_limit, TxKind::Call(self.pair), U256::ZERO, input.into())
+    }
+
+    fn sync_with_node<'a>(&'a self, rpc: &'a RpcClient) -> Pin<Box<dyn Future<Output = RpcResult<()>> + 'a>> {
+        Box::pin(self.sync_approvals(rpc))
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::time::Duration;
+
+use alloy::eips::eip2718::{Decodable2718, EIP4844_TX_TYPE_ID};
+use alloy::primitives::{hex, Address};
+use alloy_consensus::transaction::{PooledTransaction, SignerRecoverable};
+use alloy_consensus::{SignableTransaction, TxEip4844Variant, TypedTransaction};
+use rayon::prelude::*;
+use thousands::Separable;
+
+use super::tx_gen::{next_nonce, sign_and_encode_tx};
+use crate::config::ReplayFormat;
+use crate::corpus::Corpus;
+use crate::tx_queue::{QueuedTx, TX_QUEUE};
+use crate::{config, shutdown};
+
+/// Stream a pre-signed corpus into the tx queue in order, keeping at most max_queued_txs
+/// queued so the corpus is read from the page cache as it's sent rather than all at once.
+pub fn corpus_feeder(corpus: Corpus) {
+    let batch_size = config::get().tx_gen_worker.batch_size as usize;
+
+    let mut txs = corpus.iter();
+    let Some(fed) = feed_queue(|| {
+        let batch: Vec<QueuedTx> = txs
+            .by_ref()
+            .take(batch_size)
+            .map(|(sender_index, raw)| QueuedTx { sender_index, raw: raw.to_vec() })
+            .collect();
+        (!batch.is_empty()).then_some(batch)
+    }) else {
+        return;
+    };
+
+    if fed < corpus.len() {
+        println!(
+            "[!] Corpus is truncated, only {} of {} txs could be read",
+            fed.separate_with_commas(),
+            corpus.len().separate_with_commas()
+        );
+    }
+    println!("[+] Queued all {} txs from the corpus", fed.separate_with_commas());
+    shutdown::mark_source_exhausted();
+}
+
+/// Stream captured raw txs from a replay file into the tx queue in order, optionally re-signing them
+/// from our accounts. Each distinct original sender is mapped to one of our accounts (wrapping around
+/// if there are more senders than accounts), so txs from the same sender stay in the same order.
+pub fn replay_feeder(file: File) {
+    let config = &config::get().tx_source;
+    let tx_gen_config = &config::get().tx_gen_worker;
+    let batch_size = tx_gen_config.batch_size as usize;
+
+    let mut reader = ReplayReader::new(file, config.replay_format);
+    let mut sender_accounts: HashMap<Address, u32> = HashMap::new();
+    let mut skipped = 0u64;
+    let mut first_error = None;
+    let mut skip = |error: String| {
+        skipped += 1;
+        first_error.get_or_insert(error);
+    };
+
+    let Some(fed) = feed_queue(|| {
+        let mut raw_txs = Vec::with_capacity(batch_size);
+        let mut done = false;
+        while raw_txs.len() < batch_size {
+            match reader.next_tx() {
+                Some(Ok(raw)) => raw_txs.push(raw),
+                Some(Err(e)) => skip(e),
+                None => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+        if done && raw_txs.is_empty() {
+            return None;
+        }
+
+        if !config.resign {
+            // The sender index is only used to resync nonces, which replayed txs aren't sent at.
+            return Some(raw_txs.into_iter().map(|raw| QueuedTx { sender_index: 0, raw }).collect());
+        }
+
+        // Recovering senders and signing dominate, so do both in parallel and only assign accounts in order.
+        let decoded: Vec<_> = raw_txs.into_par_iter().map(|raw| decode_signed_tx(&raw)).collect();
+        let mut unsigned = Vec::with_capacity(decoded.len());
+        for result in decoded {
+            let (sender, mut tx) = match result {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    skip(e);
+                    continue;
+                }
+            };
+            let next_account = sender_accounts.len() as u32 % tx_gen_config.num_accounts;
+            let account_index = *sender_accounts.entry(sender).or_insert(next_account);
+            if config.rewrite_chain_id {
+                tx.set_chain_id(tx_gen_config.chain_id);
+            }
+            if config.rewrite_nonces {
+                set_nonce(&mut tx, next_nonce(account_index));
+            }
+            unsigned.push((account_index, tx));
+        }
+
+        Some(
+            unsigned
+                .into_par_iter()
+                .map(|(account_index, tx)| QueuedTx {
+                    sender_index: account_index,
+                    raw: sign_and_encode_tx(account_index, tx),
+                })
+                .collect(),
+        )
+    }) else {
+        return;
+    };
+
+    if skipped > 0 {
+        println!(
+            "[!] Skipped {} invalid txs in the replay file (first error: {})",
+            skipped.separate_with_commas(),
+            first_error.unwrap_or_default()
+        );
+    }
+    if config.resign {
+        println!(
+            "[*] Re-signed txs from {} original senders with {} accounts",
+            sender_accounts.len().separate_with_commas(),
+            sender_accounts.len().min(tx_gen_config.num_accounts as usize).separate_with_commas()
+        );
+    }
+    println!("[+] Queued all {} txs from the replay file", fed.separate_with_commas());
+    shutdown::mark_source_exhausted();
+}
+
+/// Push batches from `next_batch` into the tx queue until it runs out, keeping at most max_queued_txs
+/// queued. Returns the number of txs queued, or None if tx generation was stopped before then.
+fn feed_queue(mut next_batch: impl FnMut() -> Option<Vec<QueuedTx>>) -> Option<u64> {
+    let max_queued_txs = config::get().tx_source.max_queued_txs;
+
+    let mut fed = 0u64;
+    loop {
+        if shutdown::should_stop_generating() {
+            return None;
+        }
+        if TX_QUEUE.queue_len() >= max_queued_txs {
+            std::thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+
+        let Some(batch) = next_batch() else { return Some(fed) };
+        fed += batch.len() as u64;
+        TX_QUEUE.push_txs(batch);
+    }
+}
+
+/// Reads EIP-2718 encoded txs from a replay file, either one hex string per
+/// line (blank lines are ignored) or each prefixed by its u32 LE length.
+struct ReplayReader {
+    reader: BufReader<File>,
+    format: ReplayFormat,
+    line: String,
+}
+
+impl ReplayReader {
+    fn new(file: File, format: ReplayFormat) -> Self {
+        Self { reader: BufReader::new(file), format, line: String::new() }
+    }
+
+    /// Read the next tx, None at the end of the file or Err if the entry isn't valid hex.
+    fn next_tx(&mut self) -> Option<Result<Vec<u8>, String>> {
+        let read = match self.format {
+            ReplayFormat::Hex => self.next_hex_tx(),
+            ReplayFormat::Binary => self.next_binary_tx(),
+        };
+        read.unwrap_or_else(|e| panic!("[!] Failed to read replay file: {e}"))
+    }
+
+    fn next_hex_tx(&mut self) -> io::Result<Option<Result<Vec<u8>, String>>> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            let line = self.line.trim();
+            if !line.is_empty() {
+                let hex_tx = line.strip_prefix("0x").unwrap_or(line);
+                return Ok(Some(hex::decode(hex_tx).map_err(|e| format!("invalid hex: {e}"))));
+            }
+        }
+    }
+
+    fn next_binary_tx(&mut self) -> io::Result<Option<Result<Vec<u8>, String>>> {
+        let mut len = [0u8; 4];
+        match self.reader.read_exact(&mut len) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+
+        let mut raw = vec![0u8; u32::from_le_bytes(len) as usize];
+        match self.reader.read_exact(&mut raw) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                println!("[!] Replay file is truncated, its last tx is incomplete");
+                Ok(None)
+            }
+            result => result.map(|_| Some(Ok(raw))),
+        }
+    }
+}
+
+/// Decode a signed tx and recover its sender, returning it unsigned for re-signing. Blob txs must be in
+/// the network form nodes accept over RPC, with their sidecar, which is kept so they're re-signed with it.
+fn decode_signed_tx(raw: &[u8]) -> Result<(Address, TypedTransaction), String> {
+    let pooled = PooledTransaction::decode_2718(&mut &raw[..]).map_err(|e| match raw.first() {
+        Some(&EIP4844_TX_TYPE_ID) => format!("failed to decode blob tx, which needs its sidecar to be re-signed: {e}"),
+        _ => format!("failed to decode tx: {e}"),
+    })?;
+    let sender = pooled.recover_signer().map_err(|e| format!("failed to recover sender: {e}"))?;
+    Ok((sender, pooled.into_envelope().into()))
+}
+
+fn set_nonce(tx: &mut TypedTransaction, nonce: u64) {
+    match tx {
+        TypedTransaction::Legacy(tx) => tx.nonce = nonce,
+        TypedTransaction::Eip2930(tx) => tx.nonce = nonce,
+        TypedTransaction::Eip1559(tx) => tx.nonce = nonce,
+        TypedTransaction::Eip4844(TxEip4844Variant::TxEip4844(tx)) => tx.nonce = nonce,
+        TypedTransaction::Eip4844(TxEip4844Variant::TxEip4844WithSidecar(tx)) => tx.tx.nonce = nonce,
+        TypedTransaction::Eip7702(tx) => tx.nonce = nonce,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use alloy::consensus::BlobTransactionSidecar;
+    use alloy::eips::eip2718::Encodable2718;
+    use alloy::network::TxSignerSync;
+    use alloy::primitives::{TxKind, U256};
+    use alloy_consensus::{TxEip1559, TxEip4844, TxEip4844WithSidecar};
+    use alloy_signer_local::PrivateKeySigner;
+
+    use super::*;
+
+    /// A replay reader over `contents`, written to a temp file named after the test.
+    fn replay_reader(name: &str, format: ReplayFormat, contents: &[u8]) -> ReplayReader {
+        let path = std::env::temp_dir().join(format!("crescendo-{}-{name}", std::process::id()));
+        File::create(&path).unwrap().write_all(contents).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        ReplayReader::new(file, format)
+    }
+
+    fn read_all(reader: &mut ReplayReader) -> Vec<Result<Vec<u8>, String>> {
+        std::iter::from_fn(|| reader.next_tx()).collect()
+    }
+
+    fn sign(signer: &PrivateKeySigner, mut tx: TypedTransaction) -> Vec<u8> {
+        let signature = signer.sign_transaction_sync(&mut tx).unwrap();
+        tx.into_signed(signature).encoded_2718()
+    }
+
+    fn blob_tx(sidecar: Option<BlobTransactionSidecar>) -> TypedTransaction {
+        let tx = TxEip4844 {
+            chain_id: 1337,
+            nonce: 3,
+            gas_limit: 21_000,
+            max_fee_per_gas: 100_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            blob_versioned_hashes: vec![Default::default()],
+            max_fee_per_blob_gas: 1,
+            ..Default::default()
+        };
+        TypedTransaction::Eip4844(match sidecar {
+            Some(sidecar) => {
+                TxEip4844Variant::TxEip4844WithSidecar(TxEip4844WithSidecar::from_tx_and_sidecar(tx, sidecar.into()))
+            }
+            None => TxEip4844Variant::TxEip4844(tx),
+        })
+    }
+
+    #[test]
+    fn reads_hex_lines() {
+        let mut reader = replay_reader("hex", ReplayFormat::Hex, b"0x02aa\n\n  02BB \r\n0xzz\n\n0x\n03cc");
+        let txs = read_all(&mut reader);
+        assert_eq!(txs[..2], [Ok(vec![0x02, 0xaa]), Ok(vec![0x02, 0xbb])]);
+        assert!(txs[2].as_ref().unwrap_err().starts_with("invalid hex"));
+        assert_eq!(txs[3..], [Ok(vec![]), Ok(vec![0x03, 0xcc])]);
+    }
+
+    #[test]
+    fn reads_length_prefixed_raw_txs() {
+        let mut contents = Vec::new();
+        for raw in [&[0x02, 0xaa][..], &[], &[0x01; 300]] {
+            contents.extend((raw.len() as u32).to_le_bytes());
+            contents.extend(raw);
+        }
+        let txs = read_all(&mut replay_reader("raw", ReplayFormat::Binary, &contents));
+        assert_eq!(txs, [Ok(vec![0x02, 0xaa]), Ok(vec![]), Ok(vec![0x01; 300])]);
+
+        // A truncated last tx ends the file.
+        contents.extend(4u32.to_le_bytes());
+        contents.push(0x02);
+        assert_eq!(read_all(&mut replay_reader("raw-truncated", ReplayFormat::Binary, &contents)).len(), 3);
+    }
+
+    #[test]
+    fn decodes_signed_txs_for_resigning() {
+        let signer = PrivateKeySigner::random();
+        let tx = TypedTransaction::Eip1559(TxEip1559 {
+            chain_id: 1337,
+            nonce: 7,
+            gas_limit: 21_000,
+            max_fee_per_gas: 100_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            to: TxKind::Call(Address::repeat_byte(0x11)),
+            value: U256::from(5),
+            ..Default::default()
+        });
+        assert_eq!(decode_signed_tx(&sign(&signer, tx.clone())).unwrap(), (signer.address(), tx));
+        assert!(decode_signed_tx(&[0x02, 0xaa]).unwrap_err().starts_with("failed to decode tx"));
+    }
+
+    #[test]
+    fn decodes_blob_txs_with_their_sidecar() {
+        let signer = PrivateKeySigner::random();
+        let tx = blob_tx(Some(BlobTransactionSidecar::new(vec![], vec![], vec![])));
+        assert_eq!(decode_signed_tx(&sign(&signer, tx.clone())).unwrap(), (signer.address(), tx));
+
+        // Without one, re-signing would leave the tx unsendable.
+        let err = decode_signed_tx(&sign(&signer, blob_tx(None))).unwrap_err();
+        assert!(err.starts_with("failed to decode blob tx, which needs its sidecar"), "{err}");
+    }
+}
@@ -1,11 +1,12 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+use axum::body::Bytes;
 use axum::routing::get;
 use axum::{Json, Router};
 use crossbeam_utils::CachePadded;
 use mimalloc::MiMalloc;
-use serde_json::json;
+use serde_json::{json, Value};
 use thousands::Separable;
 use tokio::time::interval;
 
@@ -15,16 +16,30 @@ static GLOBAL: MiMalloc = MiMalloc;
 static TOTAL_REQUESTS: CachePadded<AtomicU64> = CachePadded::new(AtomicU64::new(0));
 static CONCURRENT_REQUESTS: CachePadded<AtomicU64> = CachePadded::new(AtomicU64::new(0));
 
-async fn handler() -> Json<serde_json::Value> {
+async fn handler(body: Bytes) -> Json<Value> {
     CONCURRENT_REQUESTS.fetch_add(1, Ordering::Relaxed);
     tokio::time::sleep(Duration::from_millis(1)).await; // Simulate processing time.
     CONCURRENT_REQUESTS.fetch_sub(1, Ordering::Relaxed);
     TOTAL_REQUESTS.fetch_add(1, Ordering::Relaxed);
-    Json(json!({
+    Json(match serde_json::from_slice(&body) {
+        // Batches get a response per request, matched up by id like a node's.
+        Ok(Value::Array(requests)) => requests.iter().map(response).collect(),
+        Ok(request) => response(&request),
+        Err(_) => response(&json!({ "id": 1 })),
+    })
+}
+
+/// A successful response to `request`, with every account at nonce 0 for nonce syncing.
+fn response(request: &Value) -> Value {
+    let result = match request["method"].as_str() {
+        Some("eth_getTransactionCount") => "0x0",
+        _ => "hello world!",
+    };
+    json!({
         "jsonrpc": "2.0",
-        "result": "hello world!",
-        "id": 1
-    }))
+        "result": result,
+        "id": request["id"]
+    })
 }
 
 #[tokio::main]